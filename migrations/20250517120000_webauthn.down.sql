@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS webauthn_ceremonies;
+DROP TABLE IF EXISTS webauthn_credentials;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS webauthn_ceremonies_expires_at_idx;
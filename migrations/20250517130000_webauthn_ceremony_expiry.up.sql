@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- expired ceremonies are cleared out whenever a new one starts
+CREATE INDEX IF NOT EXISTS webauthn_ceremonies_expires_at_idx ON webauthn_ceremonies (expires_at);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS user_identities;
+DELETE FROM users WHERE password_hash IS NULL;
+ALTER TABLE users ALTER COLUMN password_hash SET NOT NULL;
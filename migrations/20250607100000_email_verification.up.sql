@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMP;
+
+-- accounts from before verification existed are trusted as they are
+UPDATE users SET email_verified_at = created_at;
@@ -0,0 +1,13 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS login_throttles;
+
+CREATE TABLE IF NOT EXISTS login_attempts (
+    id uuid PRIMARY KEY,
+    email VARCHAR(100) NOT NULL,
+    ip VARCHAR(45),
+    succeeded BOOLEAN NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS login_attempts_email_idx ON login_attempts (email, created_at);
+CREATE INDEX IF NOT EXISTS login_attempts_ip_idx ON login_attempts (ip, created_at);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- one counter per account and per ip, so an attempt can be checked and counted in a single statement
+CREATE TABLE IF NOT EXISTS login_throttles (
+    scope VARCHAR(10) NOT NULL,
+    key VARCHAR(100) NOT NULL,
+    failures INT NOT NULL,
+    last_failure_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (scope, key)
+);
+
+DROP TABLE IF EXISTS login_attempts;
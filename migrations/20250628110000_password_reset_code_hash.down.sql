@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE password_resets DROP COLUMN IF EXISTS code_hash;
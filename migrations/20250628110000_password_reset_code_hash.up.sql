@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- the code used to travel in the reset cookie, resets started that way can't be finished anymore
+DELETE FROM password_resets;
+
+ALTER TABLE password_resets ADD COLUMN code_hash VARCHAR(255) NOT NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE users DROP COLUMN IF EXISTS role_version;
+DROP TABLE IF EXISTS user_roles;
+DROP TABLE IF EXISTS roles;
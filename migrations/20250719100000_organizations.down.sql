@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE sessions DROP COLUMN IF EXISTS active_organization_id;
+DROP TABLE IF EXISTS organization_invitations;
+DROP TABLE IF EXISTS memberships;
+DROP TABLE IF EXISTS organizations;
@@ -0,0 +1,13 @@
+-- Add down migration script here
+DROP POLICY IF EXISTS tenant_isolation ON users;
+ALTER TABLE users DISABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON organization_invitations;
+ALTER TABLE organization_invitations DISABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON memberships;
+ALTER TABLE memberships DISABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON organizations;
+ALTER TABLE organizations DISABLE ROW LEVEL SECURITY;
+
+DROP FUNCTION IF EXISTS current_tenant_id();
+DROP OWNED BY app_tenant;
+DROP ROLE IF EXISTS app_tenant;
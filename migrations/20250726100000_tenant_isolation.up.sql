@@ -0,0 +1,42 @@
+-- Add up migration script here
+
+-- tenant scoped queries run as this role (SET LOCAL ROLE, see `TenantRepository`), so row level
+-- security applies to them even when the app itself connects as the tables' owner or a superuser
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'app_tenant') THEN
+        CREATE ROLE app_tenant NOLOGIN;
+    END IF;
+END
+$$;
+
+GRANT app_tenant TO CURRENT_USER;
+GRANT USAGE ON SCHEMA public TO app_tenant;
+
+CREATE OR REPLACE FUNCTION current_tenant_id() RETURNS uuid
+LANGUAGE sql STABLE
+AS $$ SELECT NULLIF(current_setting('app.tenant_id', true), '')::uuid $$;
+
+-- tables owned by a tenant need a grant and a policy, everything else stays out of reach
+GRANT SELECT ON organizations TO app_tenant;
+ALTER TABLE organizations ENABLE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON organizations TO app_tenant
+    USING (id = current_tenant_id());
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON memberships TO app_tenant;
+ALTER TABLE memberships ENABLE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON memberships TO app_tenant
+    USING (organization_id = current_tenant_id())
+    WITH CHECK (organization_id = current_tenant_id());
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON organization_invitations TO app_tenant;
+ALTER TABLE organization_invitations ENABLE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON organization_invitations TO app_tenant
+    USING (organization_id = current_tenant_id())
+    WITH CHECK (organization_id = current_tenant_id());
+
+-- users belong to no single tenant, a tenant sees the ones that are its members
+GRANT SELECT ON users TO app_tenant;
+ALTER TABLE users ENABLE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON users TO app_tenant
+    USING (EXISTS (SELECT 1 FROM memberships WHERE memberships.user_id = users.id));
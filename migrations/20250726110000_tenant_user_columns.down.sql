@@ -0,0 +1,3 @@
+-- Add down migration script here
+REVOKE SELECT (id, name, email) ON users FROM app_tenant;
+GRANT SELECT ON users TO app_tenant;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- tenants only need to know who their members are, password hashes and the like stay out of reach
+REVOKE SELECT ON users FROM app_tenant;
+GRANT SELECT (id, name, email) ON users TO app_tenant;
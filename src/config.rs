@@ -1,5 +1,8 @@
 use serde::Deserialize;
+use crate::rate_limit::RateLimitPolicy;
+use std::collections::BTreeMap;
 use std::fs;
+use std::net::IpAddr;
 use std::path::Path;
 use std::error::Error;
 
@@ -8,6 +11,17 @@ pub struct ServerConfig {
     pub database: DatabaseConfig,
     pub app: AppConfig,
     pub mailer: MailerConfig,
+    /// OpenID Connect providers keyed by the id used in urls, e.g. `[oidc.google]`
+    #[serde(default)]
+    pub oidc: BTreeMap<String, OidcProviderConfig>,
+    #[serde(default)]
+    pub login_throttle: LoginThrottleConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub cookies: CookieConfig,
+    #[serde(default)]
+    pub jwt: JwtConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -21,6 +35,25 @@ pub struct AppConfig {
     pub origin: String,
     pub bind_address: String,
     pub jwt_secret: String,
+    #[serde(default)]
+    pub unverified_email_policy: UnverifiedEmailPolicy,
+    /// Hides whether an email has an account, see `features::auth::privacy`
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// Reverse proxies whose `X-Forwarded-For` is believed, see `utils::ClientInfo`
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// What the dashboard does for accounts that have not verified their email yet
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnverifiedEmailPolicy {
+    /// Only the verification page is shown until the email is verified
+    Block,
+    /// The dashboard is usable but shows a reminder
+    #[default]
+    Banner,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -33,6 +66,131 @@ pub struct MailerConfig {
     pub sender_name: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct OidcProviderConfig {
+    pub display_name: String,
+    /// The issuer url, discovery is done through `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default = "OidcProviderConfig::default_scopes")]
+    pub scopes: Vec<String>,
+}
+
+impl OidcProviderConfig {
+    fn default_scopes() -> Vec<String> {
+        vec!["openid".to_string(), "email".to_string(), "profile".to_string()]
+    }
+}
+
+/// Failed login limits, after `max_failures` further attempts are refused for `lockout_minutes`,
+/// doubling with every failure after that until the window passes
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoginThrottleConfig {
+    pub max_failures_per_account: i64,
+    pub max_failures_per_ip: i64,
+    pub window_minutes: i32,
+    pub lockout_minutes: i32,
+}
+
+impl Default for LoginThrottleConfig {
+    fn default() -> Self {
+        Self {
+            max_failures_per_account: 5,
+            max_failures_per_ip: 50,
+            window_minutes: 60,
+            lockout_minutes: 1,
+        }
+    }
+}
+
+/// Rate limits for the groups of api routes, see `rate_limit`
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Routes that send emails, per ip
+    pub email: RateLimitPolicy,
+    /// Routes that check credentials, per ip
+    pub auth: RateLimitPolicy,
+    /// Everything else, per user or per ip when signed out
+    pub api: RateLimitPolicy,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            email: RateLimitPolicy { burst: 5, per_minute: 1.0 },
+            auth: RateLimitPolicy { burst: 20, per_minute: 10.0 },
+            api: RateLimitPolicy { burst: 60, per_minute: 120.0 },
+        }
+    }
+}
+
+/// Attributes every auth cookie is set with
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CookieConfig {
+    pub secure: bool,
+    pub same_site: CookieSameSite,
+    /// Shares the cookies with subdomains, ignored when `host_prefix` is set
+    pub domain: Option<String>,
+    /// Names cookies `__Host-...`, which browsers only accept when secure, on `/` and without a domain
+    pub host_prefix: bool,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            secure: true,
+            same_site: CookieSameSite::Lax,
+            domain: None,
+            host_prefix: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// The keys tokens are signed with, see `features::auth::claims::keyring`.
+/// Without any keys the `JWT_SECRET` env var is used as a single HS256 key with the kid `default`
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct JwtConfig {
+    /// The `iss` of our tokens, the app origin when not set
+    pub issuer: Option<String>,
+    /// The `aud` of our tokens, the issuer when not set
+    pub audience: Option<String>,
+    /// The key new tokens are signed with, the first key when not set
+    pub active_kid: Option<String>,
+    /// Every key that still verifies tokens, remove a key to retire it
+    pub keys: Vec<JwtKeyConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    pub algorithm: JwtAlgorithm,
+    /// A PKCS#8 PEM private key, for every algorithm but HS256
+    pub private_key_file: Option<String>,
+    /// The env var holding the secret, for HS256
+    pub secret_env: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum JwtAlgorithm {
+    HS256,
+    EdDSA,
+    ES256,
+    RS256,
+}
+
 impl ServerConfig {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
         let contents = fs::read_to_string(path)?;
@@ -0,0 +1,42 @@
+use askama::Template;
+use axum::{extract::{Path, State}, response::{Html, IntoResponse}};
+use http::StatusCode;
+use uuid::Uuid;
+
+use crate::{features::{admin::permissions::{RoleChange, RoleChangePolicy, UsersWrite}, auth::permissions::{RequirePermission, Subject}}, repo::infra::{personal_access_token::PersonalAccessTokenRepo, role::RoleRepo, session::SessionRepo}, web_service::server::ServerState, ServerError};
+
+use super::views::UsersFragment;
+
+
+async fn render_users(state: &ServerState) -> Result<Html<String>, ServerError> {
+    Ok(Html(UsersFragment::load(state).await?.render()?))
+}
+
+pub async fn grant_role(State(state): State<ServerState>, subject: Subject, Path((id, role)): Path<(Uuid, String)>) -> Result<impl IntoResponse, ServerError> {
+    if !subject.can::<RoleChangePolicy, _>(&RoleChange { user_id: id, role: &role, grant: true }) {
+        return Ok((StatusCode::FORBIDDEN, "You cannot grant this role").into_response());
+    }
+
+    if !state.repo.role_grant(id, &role).await? {
+        return Ok((StatusCode::NOT_FOUND, "No such role").into_response());
+    }
+
+    Ok(render_users(&state).await?.into_response())
+}
+
+pub async fn revoke_role(State(state): State<ServerState>, subject: Subject, Path((id, role)): Path<(Uuid, String)>) -> Result<impl IntoResponse, ServerError> {
+    if !subject.can::<RoleChangePolicy, _>(&RoleChange { user_id: id, role: &role, grant: false }) {
+        return Ok((StatusCode::FORBIDDEN, "You cannot remove this role, admins cannot remove their own").into_response());
+    }
+
+    state.repo.role_revoke(id, &role).await?;
+
+    Ok(render_users(&state).await?.into_response())
+}
+
+pub async fn revoke_sessions(State(state): State<ServerState>, _: RequirePermission<UsersWrite>, Path(id): Path<Uuid>) -> Result<impl IntoResponse, ServerError> {
+    state.repo.session_revoke_all_for_user(id).await?;
+    state.repo.personal_access_token_revoke_all_for_user(id).await?;
+
+    Ok(render_users(&state).await?.into_response())
+}
@@ -0,0 +1,41 @@
+use askama::Template;
+use axum::{extract::State, response::{Html, IntoResponse}};
+use http::StatusCode;
+
+use crate::{repo::infra::role::{RoleRepo, UserWithRoles}, web_service::server::ServerState, ServerError};
+
+
+#[derive(Template)]
+#[template(path = "admin/index.html")]
+pub struct AdminTemplate {
+    users: Vec<UserWithRoles>,
+    roles: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/fragments/users.html")]
+pub struct UsersFragment {
+    pub users: Vec<UserWithRoles>,
+    pub roles: Vec<String>,
+}
+
+impl UsersFragment {
+    pub async fn load(state: &ServerState) -> Result<Self, ServerError> {
+        Ok(Self {
+            users: state.repo.user_list_with_roles().await?,
+            roles: state.repo.role_list().await?,
+        })
+    }
+}
+
+impl AdminTemplate {
+    // the router requires `UsersRead`, see `AdminService`
+    pub async fn handler(State(state): State<ServerState>) -> Result<impl IntoResponse, ServerError> {
+        let UsersFragment { users, roles } = UsersFragment::load(&state).await?;
+
+        Ok((
+            StatusCode::OK,
+            Html(Self { users, roles }.render()?)
+        ).into_response())
+    }
+}
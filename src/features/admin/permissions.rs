@@ -0,0 +1,29 @@
+use uuid::Uuid;
+
+use crate::features::auth::{permissions::{permissions, Policy, Subject}, roles::{Admin, Role}};
+
+
+permissions! {
+    /// See every account and its roles
+    UsersRead = "users:read";
+    /// Change roles and sign users out
+    UsersWrite = "users:write";
+}
+
+/// A role being granted to or taken from a user
+pub struct RoleChange<'a> {
+    pub user_id: Uuid,
+    pub role: &'a str,
+    pub grant: bool,
+}
+
+pub struct RoleChangePolicy;
+
+impl Policy<RoleChange<'_>> for RoleChangePolicy {
+    fn allows(subject: &Subject, change: &RoleChange<'_>) -> bool {
+        // someone has to stay able to hand the admin role back out
+        let demotes_self = !change.grant && change.user_id == subject.user_id() && change.role == Admin::NAME;
+
+        subject.has::<UsersWrite>() && !demotes_self
+    }
+}
@@ -1,13 +1,11 @@
 use axum::{
-    body::Body, extract::FromRequestParts, http::{header, request::Parts}, response::{IntoResponse, Response},
+    body::Body, extract::{FromRef, FromRequestParts}, http::{header, request::Parts, Method}, response::{IntoResponse, Response},
 };
-use axum_extra::extract::cookie::Cookie;
 use uuid::Uuid;
-use super::{error::ClaimsError, Claims};
-use jsonwebtoken::Validation;
+use super::{error::ClaimsError, refresh::{RefreshToken, SessionTokens}, Claims};
 use serde::{Deserialize, Serialize};
 
-use crate::{features::auth::claims::KEYS, utils::HxRedirect};
+use crate::{features::auth::csrf::is_unsafe, repo::{error::RepoError, infra::{organization::OrganizationRepo, personal_access_token::PersonalAccessTokenRepo, refresh_token::RefreshTokenRepo, role::{RoleRepo, UserRoles}, session::SessionRepo}, Repository}, utils::{bearer_token, ClientInfo, HxRedirect}, web_service::server::ServerState};
 
 
 
@@ -15,93 +13,183 @@ use crate::{features::auth::claims::KEYS, utils::HxRedirect};
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuthorizationClaim {
     pub user_id: uuid::Uuid,
+    // the server-side session this token belongs to, see `SessionRepo`.
+    // None for personal access tokens, they don't belong to a session
+    pub session_id: Option<Uuid>,
     pub exp: usize,
-    pub is_main_claims: bool //this is so it does not grab the EmailLoginAuthorizationClaim
+    // the user's roles when the token was issued, only trusted while `role_version` is current.
+    // access checks load the user's permissions fresh instead, see `Subject` and `RequirePermission`
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub role_version: i32,
+    // the organization the session works in, `ActiveOrganization` checks the membership is still there
+    #[serde(default)]
+    pub organization_id: Option<Uuid>,
+    // set when the request came with a personal access token instead of a session cookie
+    #[serde(skip)]
+    pub scopes: Option<Vec<String>>,
 
 }
 
 
-impl Claims for AuthorizationClaim 
-{
-    const EXP_TIME_HOURS: i64 = 168;
+impl Claims for AuthorizationClaim {
+    const PURPOSE: &'static str = "access";
+    // kept short, the refresh token in `SessionTokens` keeps the user signed in
+    const EXP_TIME: chrono::Duration = chrono::Duration::minutes(15);
     const SUCCESS_REDIRECT_URI: &'static str = "/dashboard";
     const COOKIE_NAME: &'static str = "token";
+}
+
+impl AuthorizationClaim {
+
+    pub fn new(user_id: Uuid, session_id: Uuid, roles: UserRoles, organization_id: Option<Uuid>) -> AuthorizationClaim{
+        let expiration_time = chrono::Utc::now() + Self::EXP_TIME;
 
-    fn cookie(&self) ->  Result<String, ClaimsError> {
-        let cookie = Cookie::build((Self::COOKIE_NAME, self.token()?.as_str()))
-            .path("/")
-            .http_only(true)
-            .to_string();
-        Ok(cookie)
+        Self {
+            user_id,
+            session_id: Some(session_id),
+            exp: expiration_time.timestamp() as usize,
+            roles: roles.roles,
+            role_version: roles.version,
+            organization_id,
+            scopes: None,
+        }
     }
 
-    fn token(&self) -> Result<String, ClaimsError> {
-        let token = jsonwebtoken::encode(&jsonwebtoken::Header::default(), self, &KEYS.encoding)?;
-        Ok(token)
+    pub fn is_personal_access_token(&self) -> bool {
+        self.scopes.is_some()
     }
 
-    fn from_token(token: String) -> Result<Self, ClaimsError> where Self: Sized {
-        let token_data = jsonwebtoken::decode(token.as_str(), &KEYS.decoding, &Validation::default())
-        .map_err(|e| {
-            tracing::debug!("Error decoding token: {:?}", e);
-            ClaimsError::InvalidToken
-        })?;
+    /// Signs in with a personal access token, `read` allows safe methods and `write` the others
+    async fn from_personal_access_token(repo: &Repository, token: &str, method: &Method) -> Result<Self, ClaimsError> {
+        let token = repo
+            .personal_access_token_touch(token)
+            .await?
+            .ok_or(ClaimsError::InvalidAccessToken)?;
+
+        let required = if is_unsafe(method) { SCOPE_WRITE } else { SCOPE_READ };
+        if !token.scopes.iter().any(|scope| scope == required) {
+            return Err(ClaimsError::InsufficientScope);
+        }
 
-        return Ok(token_data.claims)    
+        let roles = repo.user_roles(token.user_id).await?;
+        let organization_id = repo.session_active_organization(None, token.user_id).await?;
+
+        Ok(Self {
+            user_id: token.user_id,
+            session_id: None,
+            exp: (chrono::Utc::now() + Self::EXP_TIME).timestamp() as usize,
+            roles: roles.roles,
+            role_version: roles.version,
+            organization_id,
+            scopes: Some(token.scopes),
+        })
     }
-}
 
-impl AuthorizationClaim {
+    /// A fresh access claim for an existing session with the user's current roles and organization
+    pub async fn issue(repo: &Repository, user_id: Uuid, session_id: Uuid) -> Result<Self, RepoError> {
+        let roles = repo.user_roles(user_id).await?;
+        let organization_id = repo.session_active_organization(Some(session_id), user_id).await?;
 
-    pub fn new(user_id: Uuid) -> AuthorizationClaim{
-        let expiration_time = chrono::Utc::now() + chrono::Duration::hours(Self::EXP_TIME_HOURS);
+        Ok(Self::new(user_id, session_id, roles, organization_id))
+    }
 
+    /// The same claim switched to another organization, with a new expiry
+    pub fn with_organization(self, organization_id: Uuid) -> Self {
         Self {
-            user_id,
-            exp: expiration_time.timestamp() as usize,
-            is_main_claims: true
+            organization_id: Some(organization_id),
+            exp: (chrono::Utc::now() + Self::EXP_TIME).timestamp() as usize,
+            ..self
         }
     }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    /// Records a new session for the user and returns an access claim bound to it
+    /// together with the session's first refresh token
+    pub async fn start_session(repo: &Repository, user_id: Uuid, client: &ClientInfo) -> Result<SessionTokens, RepoError> {
+        let session_id = repo
+            .session_create(user_id, client.user_agent.as_deref(), client.ip.as_deref())
+            .await?;
+
+        let refresh_token = repo.refresh_token_create(session_id).await?;
+
+        Ok(SessionTokens {
+            access: Self::issue(repo, user_id, session_id).await?,
+            refresh_token: RefreshToken(refresh_token),
+        })
+    }
 }
 
-// Extract JWT from cookie
+pub const SCOPE_READ: &str = "read";
+pub const SCOPE_WRITE: &str = "write";
+
+// Extract JWT from cookie, or a personal access token from the authorization header
 impl<S> FromRequestParts<S> for AuthorizationClaim
 where
+    ServerState: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = ClaimsError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let cookies= parts.headers.get(header::COOKIE);
-        
-        match cookies {
-            Some(cookies) => {
-                // parse the cookies and get the token
-                let cookies_str = cookies.to_str().map_err(|_| ClaimsError::InvalidToken)?;
-                
-                let token= crate::utils::extract_cookie_value(cookies_str, Self::COOKIE_NAME);
-
-                if token.is_none() {
-                    return Err(ClaimsError::TokenNotFound)
-                }
-                let token = token.unwrap();
-
-                tracing::debug!("Token: {:?}", token);
-
-                let token_data = jsonwebtoken::decode(token.as_str(), &KEYS.decoding, &Validation::default())
-                    .map_err(|e| {
-                        tracing::debug!("Error decoding token: {:?}", e);
-                        ClaimsError::InvalidToken
-                    })?;
-                return Ok(token_data.claims)
-
-            },
-            None => return Err(ClaimsError::TokenNotFound )
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // scripts send a personal access token instead of cookies
+        if let Some(token) = bearer_token(&parts.headers) {
+            let state = ServerState::from_ref(state);
+            return Self::from_personal_access_token(&state.repo, token, &parts.method).await;
         }
 
+        Self::from_cookie(parts, state).await.map_err(|err| err.login_required(parts))
+    }
+}
+
+impl AuthorizationClaim {
+    async fn from_cookie<S>(parts: &Parts, state: &S) -> Result<Self, ClaimsError>
+    where
+        ServerState: FromRef<S>,
+    {
+        let claims = Self::from_cookies(&parts.headers)?;
+
+        // the jwt alone is not enough, the session must still be active
+        let state = ServerState::from_ref(state);
+        let Some(session_id) = claims.session_id else {
+            return Err(ClaimsError::SessionRevoked);
+        };
+        let session = state.repo.session_touch(session_id).await?;
+
+        match session {
+            Some(session) if session.user_id == claims.user_id => Ok(claims),
+            _ => Err(ClaimsError::SessionRevoked)
+        }
     }
 }
 
+/// The signed in user on a browser session, personal access tokens are refused.
+/// Routes that change how the account signs in or what the session works on need it, a leaked
+/// script token must not be enough to take the account over
+#[derive(Debug, Clone)]
+pub struct RequireSession {
+    pub claim: AuthorizationClaim,
+    pub session_id: Uuid,
+}
+
+impl<S> FromRequestParts<S> for RequireSession
+where
+    ServerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ClaimsError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claim = AuthorizationClaim::from_request_parts(parts, state).await?;
+        let session_id = claim.session_id.ok_or(ClaimsError::InsufficientScope)?;
+
+        Ok(Self { claim, session_id })
+    }
+}
 
 impl IntoResponse for AuthorizationClaim {
     fn into_response(self) -> Response {
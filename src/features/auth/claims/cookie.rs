@@ -0,0 +1,67 @@
+use std::sync::OnceLock;
+
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use tower_cookies::cookie::time;
+
+use crate::config::{CookieConfig, CookieSameSite};
+
+
+/// Set once from the config when the server starts, the defaults are used until then
+pub static COOKIE_POLICY: OnceLock<CookieConfig> = OnceLock::new();
+
+fn policy() -> &'static CookieConfig {
+    COOKIE_POLICY.get_or_init(CookieConfig::default)
+}
+
+/// The name the browser stores the cookie under, `__Host-` prefixed when the policy asks for it
+pub fn name(base: &str) -> String {
+    if policy().host_prefix {
+        format!("__Host-{}", base)
+    } else {
+        base.to_string()
+    }
+}
+
+/// Builds a cookie with the configured attributes, `max_age` None makes it a session cookie
+pub fn build(base: &str, value: String, max_age: Option<chrono::Duration>, same_site: Option<SameSite>) -> String {
+    let mut cookie = base_cookie(base, value, same_site);
+
+    if let Some(max_age) = max_age {
+        cookie.set_max_age(time::Duration::seconds(max_age.num_seconds()));
+    }
+
+    cookie.to_string()
+}
+
+/// An expired cookie that replaces `base`, it has to match the original's name, path and domain
+pub fn removal(base: &str, same_site: Option<SameSite>) -> String {
+    let mut cookie = base_cookie(base, String::new(), same_site);
+    cookie.set_max_age(time::Duration::ZERO);
+
+    cookie.to_string()
+}
+
+fn base_cookie(base: &str, value: String, same_site: Option<SameSite>) -> Cookie<'static> {
+    let policy = policy();
+
+    let same_site = same_site.unwrap_or(match policy.same_site {
+        CookieSameSite::Strict => SameSite::Strict,
+        CookieSameSite::Lax => SameSite::Lax,
+        CookieSameSite::None => SameSite::None,
+    });
+
+    let mut cookie = Cookie::build((name(base), value))
+        .path("/")
+        .http_only(true)
+        // browsers drop SameSite=None cookies that are not secure, and __Host- requires it
+        .secure(policy.secure || policy.host_prefix || same_site == SameSite::None)
+        .same_site(same_site)
+        .build();
+
+    // a __Host- cookie must not have a domain
+    if let Some(domain) = &policy.domain && !policy.host_prefix {
+        cookie.set_domain(domain.clone());
+    }
+
+    cookie
+}
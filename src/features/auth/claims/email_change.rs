@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::Claims;
+
+/// The signed token inside the links of an email change. The confirmation goes to the new
+/// address, the revert link to the old one, `action` keeps the two from being swapped
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmailChangeClaim {
+    pub change_id: Uuid,
+    pub action: String,
+    pub exp: usize,
+}
+
+impl EmailChangeClaim {
+    pub const EXPIRE_TIME_HOURS: i64 = 24;
+    // the old owner may only notice days later that their email was changed
+    pub const REVERT_EXPIRE_TIME_DAYS: i64 = 7;
+    const CONFIRM: &'static str = "confirm";
+    const REVERT: &'static str = "revert";
+
+    pub fn confirm(change_id: Uuid) -> Self {
+        let expiration_time = chrono::Utc::now() + Self::EXP_TIME;
+
+        Self {
+            change_id,
+            action: Self::CONFIRM.to_string(),
+            exp: expiration_time.timestamp() as usize,
+        }
+    }
+
+    pub fn revert(change_id: Uuid) -> Self {
+        let expiration_time = chrono::Utc::now() + chrono::Duration::days(Self::REVERT_EXPIRE_TIME_DAYS);
+
+        Self {
+            change_id,
+            action: Self::REVERT.to_string(),
+            exp: expiration_time.timestamp() as usize,
+        }
+    }
+
+    pub fn is_confirm(&self) -> bool {
+        self.action == Self::CONFIRM
+    }
+
+    pub fn is_revert(&self) -> bool {
+        self.action == Self::REVERT
+    }
+}
+
+impl Claims for EmailChangeClaim {
+    const PURPOSE: &'static str = "email_change";
+    const EXP_TIME: chrono::Duration = chrono::Duration::hours(Self::EXPIRE_TIME_HOURS);
+    const SUCCESS_REDIRECT_URI: &'static str = "/dashboard";
+    const COOKIE_NAME: &'static str = "email_change";
+}
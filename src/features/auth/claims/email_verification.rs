@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::Claims;
+
+/// The signed token inside an emailed verification link. It is bound to the address it was
+/// sent to, so it stops working if the user's email changes before it is used
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmailVerificationClaim {
+    pub user_id: Uuid,
+    pub email: String,
+    pub exp: usize,
+}
+
+impl EmailVerificationClaim {
+    pub const EXPIRE_TIME_HOURS: i64 = 24;
+
+    pub fn new(user_id: Uuid, email: String) -> Self {
+        let expiration_time = chrono::Utc::now() + Self::EXP_TIME;
+
+        Self {
+            user_id,
+            email,
+            exp: expiration_time.timestamp() as usize,
+        }
+    }
+}
+
+impl Claims for EmailVerificationClaim {
+    const PURPOSE: &'static str = "email_verification";
+    const EXP_TIME: chrono::Duration = chrono::Duration::hours(Self::EXPIRE_TIME_HOURS);
+    const SUCCESS_REDIRECT_URI: &'static str = "/dashboard";
+    const COOKIE_NAME: &'static str = "email_verification";
+}
@@ -1,5 +1,7 @@
 use axum::response::{IntoResponse, Response};
-use http::StatusCode;
+use http::{header, request::Parts, StatusCode};
+
+use crate::{features::auth::return_to, repo::error::RepoError, utils::{is_htmx_request, HxRedirect}};
 
 
 
@@ -7,22 +9,64 @@ use http::StatusCode;
 pub enum ClaimsError {
     TokenNotFound,
     InvalidToken,
+    // the token is valid but its server-side session was revoked or never existed
+    SessionRevoked,
+    // the token is valid but the user it belongs to is gone
+    UserNotFound,
+    // one of the above, with what the rejected request needs to get the visitor signed in and back
+    LoginRequired { htmx: bool, next: Option<String> },
+    // the bearer token is not a valid personal access token
+    InvalidAccessToken,
+    // the personal access token lacks the scope for this request
+    InsufficientScope,
+    #[from]
+    JsonWebToken(jsonwebtoken::errors::Error),
     #[from]
-    JsonWebToken(jsonwebtoken::errors::Error)
+    RepoError(RepoError),
 }
 
 
 
+impl ClaimsError {
+    /// For extractors of the signed in user, a missing or dead session becomes `LoginRequired`
+    pub fn login_required(self, parts: &Parts) -> Self {
+        match self {
+            Self::TokenNotFound | Self::InvalidToken | Self::SessionRevoked | Self::UserNotFound => Self::LoginRequired {
+                htmx: is_htmx_request(&parts.headers),
+                next: return_to::from_request(parts),
+            },
+            err => err,
+        }
+    }
+}
+
 impl IntoResponse for ClaimsError {
     fn into_response(self) -> Response {
         match self {
             Self::InvalidToken=> {
-                return axum::response::Redirect::to("/auth/login").into_response()
+                axum::response::Redirect::to("/auth/login").into_response()
             },
             Self::TokenNotFound => {
-                return axum::response::Redirect::to("/auth/login").into_response()
+                axum::response::Redirect::to("/auth/login").into_response()
                 // (StatusCode::UNAUTHORIZED, [(HxRedirect::HEADER_NAME, Self::REDIRECT_URI)]).into_response()
             },
+            Self::SessionRevoked | Self::UserNotFound => {
+                axum::response::Redirect::to("/auth/login").into_response()
+            },
+            // htmx would follow a redirect inside the swap target and put the login page into a fragment
+            Self::LoginRequired { htmx: true, next } => {
+                (StatusCode::UNAUTHORIZED, [(HxRedirect::HEADER_NAME, return_to::login_url(next.as_deref()))]).into_response()
+            },
+            Self::LoginRequired { htmx: false, next } => {
+                axum::response::Redirect::to(&return_to::login_url(next.as_deref())).into_response()
+            },
+            // api clients get a status code, a redirect to the login page is no use to them
+            Self::InvalidAccessToken => {
+                (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], "Invalid or expired access token").into_response()
+            },
+            Self::InsufficientScope => {
+                (StatusCode::FORBIDDEN, [(header::WWW_AUTHENTICATE, "Bearer error=\"insufficient_scope\"")], "This access token does not have the scope for this request").into_response()
+            },
             // this is for all errors that can be considered internal server errors from crates or smthn
             err => {
                 tracing::error!("A claims error occured: {:?}", err);
@@ -30,4 +74,4 @@ impl IntoResponse for ClaimsError {
             }
         }
     }
-}
\ No newline at end of file
+}
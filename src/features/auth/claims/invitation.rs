@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::Claims;
+use crate::repo::infra::organization::INVITATION_EXP_DAYS;
+
+/// The signed token in an organization invitation link, `jti` is the invitation's id
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InvitationClaim {
+    pub jti: Uuid,
+    pub exp: usize,
+}
+
+impl InvitationClaim {
+    pub fn new(invitation_id: Uuid) -> Self {
+        let expiration_time = chrono::Utc::now() + Self::EXP_TIME;
+
+        Self {
+            jti: invitation_id,
+            exp: expiration_time.timestamp() as usize,
+        }
+    }
+}
+
+impl Claims for InvitationClaim {
+    const PURPOSE: &'static str = "organization_invitation";
+    const EXP_TIME: chrono::Duration = chrono::Duration::days(INVITATION_EXP_DAYS as i64);
+    const SUCCESS_REDIRECT_URI: &'static str = "/dashboard";
+    const COOKIE_NAME: &'static str = "organization_invitation";
+}
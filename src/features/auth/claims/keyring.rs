@@ -0,0 +1,505 @@
+//! The keys our JWTs are signed with. Every token names its key in the `kid` header, so keys can be
+//! rotated: new tokens use the active key while the others keep verifying what they signed until
+//! they are removed from the config. The public halves are served at `/.well-known/jwks.json`.
+//!
+//! Tokens also carry who issued them (`iss`), who they are for (`aud`) and what they are for (`typ`),
+//! so neither a token from another deployment sharing a key nor one issued for another purpose is accepted.
+
+use std::{collections::HashMap, error::Error, fs, sync::OnceLock};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    errors::ErrorKind,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters, EllipticCurveKeyType, Jwk,
+        JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use ring::{
+    rand::SystemRandom,
+    rsa::PublicKeyComponents,
+    signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::config::{JwtAlgorithm, JwtConfig, JwtKeyConfig};
+
+
+/// Set once from the config when the server starts
+pub static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+pub fn keyring() -> &'static Keyring {
+    KEYRING.get().expect("the keyring is loaded when the server starts")
+}
+
+/// Signs `claims` for `purpose` with the active key
+pub fn encode<T: Serialize>(purpose: &str, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+    keyring().encode(purpose, claims)
+}
+
+/// Verifies `token` with the key named in its header, along with its expiry, issuer, audience and purpose
+pub fn decode<T: DeserializeOwned>(purpose: &str, token: &str) -> Result<T, jsonwebtoken::errors::Error> {
+    keyring().decode(purpose, token)
+}
+
+/// What every token carries next to its own claims
+#[derive(Serialize, Deserialize)]
+struct Envelope<C> {
+    iss: String,
+    aud: String,
+    typ: String,
+    #[serde(flatten)]
+    claims: C,
+}
+
+// tokens from before the keyring have no kid, they were signed with `JWT_SECRET`
+const DEFAULT_KID: &str = "default";
+
+struct SigningKey {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    // None for shared secrets, those must never be published
+    public: Option<AlgorithmParameters>,
+}
+
+pub struct Keyring {
+    issuer: String,
+    audience: String,
+    active_kid: String,
+    keys: HashMap<String, SigningKey>,
+}
+
+impl Keyring {
+    /// `origin` is the issuer and audience unless the config names others
+    pub fn from_config(config: &JwtConfig, origin: &str) -> Result<Self, Box<dyn Error>> {
+        let issuer = config.issuer.clone().unwrap_or_else(|| origin.to_string());
+        let audience = config.audience.clone().unwrap_or_else(|| issuer.clone());
+
+        if config.keys.is_empty() {
+            let secret = std::env::var("JWT_SECRET").map_err(|_| "JWT_SECRET must be set when no jwt keys are configured")?;
+
+            return Ok(Self {
+                issuer,
+                audience,
+                active_kid: DEFAULT_KID.to_string(),
+                keys: HashMap::from([(DEFAULT_KID.to_string(), SigningKey::hmac(secret.as_bytes()))]),
+            });
+        }
+
+        let mut keys = HashMap::new();
+        for key in &config.keys {
+            if keys.insert(key.kid.clone(), SigningKey::load(key)?).is_some() {
+                return Err(format!("jwt key {} is configured twice", key.kid).into());
+            }
+        }
+
+        let active_kid = config.active_kid.clone().unwrap_or_else(|| config.keys[0].kid.clone());
+        if !keys.contains_key(&active_kid) {
+            return Err(format!("the active jwt key {} is not configured", active_kid).into());
+        }
+
+        Ok(Self { issuer, audience, active_kid, keys })
+    }
+
+    pub fn encode<T: Serialize>(&self, purpose: &str, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let key = &self.keys[&self.active_kid];
+
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(self.active_kid.clone());
+
+        let envelope = Envelope {
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            typ: purpose.to_string(),
+            claims,
+        };
+
+        jsonwebtoken::encode(&header, &envelope, &key.encoding)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, purpose: &str, token: &str) -> Result<T, jsonwebtoken::errors::Error> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let kid = header.kid.as_deref().unwrap_or(DEFAULT_KID);
+
+        // a retired or unknown key is the same as a bad signature
+        let key = self.keys.get(kid).ok_or(ErrorKind::InvalidSignature)?;
+
+        // only the key's own algorithm, a token can't pick a weaker one (or an HMAC over a public key)
+        let mut validation = Validation::new(key.algorithm);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+        let envelope = jsonwebtoken::decode::<Envelope<T>>(token, &key.decoding, &validation)?.claims;
+
+        if envelope.typ != purpose {
+            return Err(ErrorKind::InvalidToken.into());
+        }
+
+        Ok(envelope.claims)
+    }
+
+    /// The public keys, for other services verifying our tokens
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys: Vec<Jwk> = self.keys
+            .iter()
+            .filter_map(|(kid, key)| {
+                Some(Jwk {
+                    common: CommonParameters {
+                        public_key_use: Some(PublicKeyUse::Signature),
+                        key_algorithm: Some(key_algorithm(key.algorithm)),
+                        key_id: Some(kid.clone()),
+                        ..Default::default()
+                    },
+                    algorithm: key.public.clone()?,
+                })
+            })
+            .collect();
+
+        // the active key first, the rest in a stable order
+        keys.sort_by_key(|jwk| (jwk.common.key_id.as_deref() != Some(&self.active_kid), jwk.common.key_id.clone()));
+
+        JwkSet { keys }
+    }
+}
+
+impl SigningKey {
+    fn hmac(secret: &[u8]) -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            public: None,
+        }
+    }
+
+    fn load(config: &JwtKeyConfig) -> Result<Self, Box<dyn Error>> {
+        // ring gives us the public halves, the private key file is all that needs to be deployed
+        let key = match config.algorithm {
+            JwtAlgorithm::HS256 => {
+                let var = config.secret_env.as_deref().ok_or_else(|| format!("jwt key {} needs secret_env", config.kid))?;
+                let secret = std::env::var(var).map_err(|_| format!("{} must be set for jwt key {}", var, config.kid))?;
+
+                Self::hmac(secret.as_bytes())
+            },
+            JwtAlgorithm::EdDSA => {
+                let (pem_bytes, pem) = read_private_key(config)?;
+                let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pem.contents()).map_err(|err| err.to_string())?;
+                let x = URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref());
+
+                Self {
+                    algorithm: Algorithm::EdDSA,
+                    encoding: EncodingKey::from_ed_pem(&pem_bytes)?,
+                    decoding: DecodingKey::from_ed_components(&x)?,
+                    public: Some(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x,
+                    })),
+                }
+            },
+            JwtAlgorithm::ES256 => {
+                let (pem_bytes, pem) = read_private_key(config)?;
+                let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pem.contents(), &SystemRandom::new())
+                    .map_err(|err| err.to_string())?;
+                // an uncompressed point, 0x04 followed by x and y
+                let point = key_pair.public_key().as_ref();
+                let x = URL_SAFE_NO_PAD.encode(&point[1..33]);
+                let y = URL_SAFE_NO_PAD.encode(&point[33..65]);
+
+                Self {
+                    algorithm: Algorithm::ES256,
+                    encoding: EncodingKey::from_ec_pem(&pem_bytes)?,
+                    decoding: DecodingKey::from_ec_components(&x, &y)?,
+                    public: Some(AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x,
+                        y,
+                    })),
+                }
+            },
+            JwtAlgorithm::RS256 => {
+                let (pem_bytes, pem) = read_private_key(config)?;
+                // `openssl genrsa` writes PKCS#1, `openssl genpkey` PKCS#8
+                let key_pair = match pem.tag() {
+                    "RSA PRIVATE KEY" => RsaKeyPair::from_der(pem.contents()),
+                    _ => RsaKeyPair::from_pkcs8(pem.contents()),
+                }
+                .map_err(|err| err.to_string())?;
+                let components = PublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+
+                Self {
+                    algorithm: Algorithm::RS256,
+                    encoding: EncodingKey::from_rsa_pem(&pem_bytes)?,
+                    decoding: DecodingKey::from_rsa_raw_components(&components.n, &components.e),
+                    public: Some(AlgorithmParameters::RSA(RSAKeyParameters {
+                        key_type: RSAKeyType::RSA,
+                        n: URL_SAFE_NO_PAD.encode(&components.n),
+                        e: URL_SAFE_NO_PAD.encode(&components.e),
+                    })),
+                }
+            },
+        };
+
+        Ok(key)
+    }
+}
+
+fn read_private_key(config: &JwtKeyConfig) -> Result<(Vec<u8>, pem::Pem), Box<dyn Error>> {
+    let path = config.private_key_file.as_deref().ok_or_else(|| format!("jwt key {} needs private_key_file", config.kid))?;
+    let pem_bytes = fs::read(path).map_err(|err| format!("could not read jwt key {}: {}", path, err))?;
+    let pem = pem::parse(&pem_bytes)?;
+
+    Ok((pem_bytes, pem))
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        _ => KeyAlgorithm::HS256,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use ring::signature::ECDSA_P256_SHA256_FIXED_SIGNING;
+
+    use super::*;
+
+    const ORIGIN: &str = "http://localhost:8000";
+    const SECRET: &[u8] = b"a secret only the tests know";
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestClaim {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claim() -> TestClaim {
+        TestClaim { sub: "alice".to_string(), exp: (chrono::Utc::now() + chrono::Duration::minutes(5)).timestamp() as usize }
+    }
+
+    fn keyring(active_kid: &str, keys: Vec<(&str, SigningKey)>) -> Keyring {
+        Keyring {
+            issuer: ORIGIN.to_string(),
+            audience: ORIGIN.to_string(),
+            active_kid: active_kid.to_string(),
+            keys: keys.into_iter().map(|(kid, key)| (kid.to_string(), key)).collect(),
+        }
+    }
+
+    // a fresh PKCS#8 key in a pem file like the ones `openssl genpkey` writes, removed when dropped
+    struct KeyFile(PathBuf);
+
+    impl Drop for KeyFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn private_key_file(pkcs8: &[u8]) -> KeyFile {
+        let path = std::env::temp_dir().join(format!("jwt-key-{}.pem", uuid::Uuid::new_v4()));
+        fs::write(&path, pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.to_vec()))).unwrap();
+        KeyFile(path)
+    }
+
+    fn ed25519_key_file() -> KeyFile {
+        private_key_file(Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap().as_ref())
+    }
+
+    fn es256_key_file() -> KeyFile {
+        private_key_file(EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new()).unwrap().as_ref())
+    }
+
+    fn key_config(kid: &str, algorithm: JwtAlgorithm, file: &KeyFile) -> JwtKeyConfig {
+        JwtKeyConfig {
+            kid: kid.to_string(),
+            algorithm,
+            private_key_file: Some(file.0.to_str().unwrap().to_string()),
+            secret_env: None,
+        }
+    }
+
+    fn load(kid: &str, algorithm: JwtAlgorithm, file: &KeyFile) -> SigningKey {
+        SigningKey::load(&key_config(kid, algorithm, file)).unwrap()
+    }
+
+    fn kid(token: &str) -> Option<String> {
+        jsonwebtoken::decode_header(token).unwrap().kid
+    }
+
+    #[test]
+    fn signs_with_the_active_key() {
+        let keyring = keyring("new", vec![("old", SigningKey::hmac(b"old secret")), ("new", SigningKey::hmac(SECRET))]);
+
+        let token = keyring.encode("access", &claim()).unwrap();
+
+        assert_eq!(kid(&token).as_deref(), Some("new"));
+        assert_eq!(keyring.decode::<TestClaim>("access", &token).unwrap().sub, "alice");
+    }
+
+    #[test]
+    fn old_keys_keep_verifying_until_they_are_retired() {
+        let before = keyring("old", vec![("old", SigningKey::hmac(b"old secret"))]);
+        let token = before.encode("access", &claim()).unwrap();
+
+        let rotated = keyring("new", vec![("old", SigningKey::hmac(b"old secret")), ("new", SigningKey::hmac(SECRET))]);
+        assert!(rotated.decode::<TestClaim>("access", &token).is_ok());
+
+        let retired = keyring("new", vec![("new", SigningKey::hmac(SECRET))]);
+        let err = retired.decode::<TestClaim>("access", &token).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidSignature);
+    }
+
+    #[test]
+    fn refuses_a_token_naming_another_key() {
+        let keyring = keyring("a", vec![("a", SigningKey::hmac(b"secret a")), ("b", SigningKey::hmac(b"secret b"))]);
+        let token = keyring.encode("access", &claim()).unwrap();
+
+        // the same token relabelled as signed by the other key
+        let (_, rest) = token.split_once('.').unwrap();
+        let header = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&Header { kid: Some("b".to_string()), ..Header::new(Algorithm::HS256) }).unwrap());
+
+        assert!(keyring.decode::<TestClaim>("access", &format!("{}.{}", header, rest)).is_err());
+    }
+
+    #[test]
+    fn tokens_without_a_kid_use_the_default_key() {
+        let keyring = keyring(DEFAULT_KID, vec![(DEFAULT_KID, SigningKey::hmac(SECRET))]);
+
+        let envelope = Envelope { iss: ORIGIN.to_string(), aud: ORIGIN.to_string(), typ: "access".to_string(), claims: claim() };
+        let token = jsonwebtoken::encode(&Header::default(), &envelope, &EncodingKey::from_secret(SECRET)).unwrap();
+
+        assert_eq!(keyring.decode::<TestClaim>("access", &token).unwrap(), envelope.claims);
+    }
+
+    #[test]
+    fn signs_and_verifies_with_asymmetric_keys() {
+        let ed25519 = ed25519_key_file();
+        let es256 = es256_key_file();
+
+        for (algorithm, file) in [(JwtAlgorithm::EdDSA, &ed25519), (JwtAlgorithm::ES256, &es256)] {
+            let keyring = keyring("key", vec![("key", load("key", algorithm, file))]);
+            let token = keyring.encode("access", &claim()).unwrap();
+
+            assert_eq!(keyring.decode::<TestClaim>("access", &token).unwrap().sub, "alice");
+        }
+    }
+
+    #[test]
+    fn refuses_another_algorithm_than_the_keys_own() {
+        let file = ed25519_key_file();
+        let keyring = keyring("ed", vec![("ed", load("ed", JwtAlgorithm::EdDSA, &file))]);
+
+        // an HMAC made with the public key, which anyone can read from the jwks
+        let public_key = match keyring.jwks().keys[0].algorithm.clone() {
+            AlgorithmParameters::OctetKeyPair(params) => params.x,
+            _ => unreachable!(),
+        };
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("ed".to_string());
+        let envelope = Envelope { iss: ORIGIN.to_string(), aud: ORIGIN.to_string(), typ: "access".to_string(), claims: claim() };
+        let token = jsonwebtoken::encode(&header, &envelope, &EncodingKey::from_secret(public_key.as_bytes())).unwrap();
+
+        let err = keyring.decode::<TestClaim>("access", &token).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidAlgorithm);
+    }
+
+    #[test]
+    fn publishes_only_public_keys() {
+        let file = es256_key_file();
+        let keyring = keyring("hmac", vec![("hmac", SigningKey::hmac(SECRET)), ("ec", load("ec", JwtAlgorithm::ES256, &file))]);
+
+        let jwks = keyring.jwks();
+
+        assert_eq!(jwks.keys.len(), 1);
+        assert_eq!(jwks.keys[0].common.key_id.as_deref(), Some("ec"));
+        assert!(matches!(jwks.keys[0].algorithm, AlgorithmParameters::EllipticCurve(_)));
+    }
+
+    #[test]
+    fn refuses_configs_it_cant_sign_with() {
+        let file = ed25519_key_file();
+        let config = |active_kid: Option<&str>, kids: &[&str]| JwtConfig {
+            issuer: None,
+            audience: None,
+            active_kid: active_kid.map(str::to_string),
+            keys: kids.iter().map(|kid| key_config(kid, JwtAlgorithm::EdDSA, &file)).collect(),
+        };
+
+        assert!(Keyring::from_config(&config(None, &["a", "b"]), ORIGIN).is_ok());
+        assert!(Keyring::from_config(&config(Some("c"), &["a", "b"]), ORIGIN).is_err());
+        assert!(Keyring::from_config(&config(None, &["a", "a"]), ORIGIN).is_err());
+    }
+
+    // another deployment or service sharing our signing key
+    fn keyring_for(issuer: &str, audience: &str) -> Keyring {
+        Keyring {
+            issuer: issuer.to_string(),
+            audience: audience.to_string(),
+            ..keyring("key", vec![("key", SigningKey::hmac(SECRET))])
+        }
+    }
+
+    #[test]
+    fn refuses_a_token_issued_for_another_purpose() {
+        let keyring = keyring("key", vec![("key", SigningKey::hmac(SECRET))]);
+        let token = keyring.encode("password_reset", &claim()).unwrap();
+
+        assert!(keyring.decode::<TestClaim>("password_reset", &token).is_ok());
+        assert_eq!(keyring.decode::<TestClaim>("access", &token).unwrap_err().kind(), &ErrorKind::InvalidToken);
+    }
+
+    #[test]
+    fn refuses_a_token_for_another_audience() {
+        let ours = keyring_for(ORIGIN, ORIGIN);
+        let token = keyring_for(ORIGIN, "https://other.example").encode("access", &claim()).unwrap();
+
+        assert_eq!(ours.decode::<TestClaim>("access", &token).unwrap_err().kind(), &ErrorKind::InvalidAudience);
+    }
+
+    #[test]
+    fn refuses_a_token_from_another_issuer() {
+        let ours = keyring_for(ORIGIN, ORIGIN);
+        let token = keyring_for("https://other.example", ORIGIN).encode("access", &claim()).unwrap();
+
+        assert_eq!(ours.decode::<TestClaim>("access", &token).unwrap_err().kind(), &ErrorKind::InvalidIssuer);
+    }
+
+    #[test]
+    fn refuses_a_token_without_issuer_audience_or_purpose() {
+        let keyring = keyring("key", vec![("key", SigningKey::hmac(SECRET))]);
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("key".to_string());
+
+        // signed with our key, but without the envelope
+        let token = jsonwebtoken::encode(&header, &claim(), &EncodingKey::from_secret(SECRET)).unwrap();
+        assert!(keyring.decode::<TestClaim>("access", &token).is_err());
+
+        #[derive(Serialize)]
+        struct WithoutPurpose {
+            iss: &'static str,
+            aud: &'static str,
+            #[serde(flatten)]
+            claims: TestClaim,
+        }
+        let token = jsonwebtoken::encode(&header, &WithoutPurpose { iss: ORIGIN, aud: ORIGIN, claims: claim() }, &EncodingKey::from_secret(SECRET)).unwrap();
+        assert!(keyring.decode::<TestClaim>("access", &token).is_err());
+    }
+
+    #[test]
+    fn refuses_an_expired_token() {
+        let keyring = keyring("key", vec![("key", SigningKey::hmac(SECRET))]);
+        let expired = TestClaim { exp: (chrono::Utc::now() - chrono::Duration::minutes(5)).timestamp() as usize, ..claim() };
+        let token = keyring.encode("access", &expired).unwrap();
+
+        assert_eq!(keyring.decode::<TestClaim>("access", &token).unwrap_err().kind(), &ErrorKind::ExpiredSignature);
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::Claims;
+
+/// The signed token inside an emailed sign in link, it travels in the url rather than a cookie.
+/// `jti` is recorded in the database so each link can only be used once
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MagicLinkClaim {
+    pub jti: Uuid,
+    pub user_id: Uuid,
+    // where to go once signed in, already sanitized, see `return_to`
+    #[serde(default)]
+    pub next: Option<String>,
+    pub exp: usize,
+}
+
+impl MagicLinkClaim {
+    pub const EXPIRE_TIME_MINUTES: i64 = 15;
+
+    pub fn new(user_id: Uuid, next: Option<String>) -> Self {
+        let expiration_time = chrono::Utc::now() + Self::EXP_TIME;
+
+        Self {
+            jti: Uuid::new_v4(),
+            user_id,
+            next,
+            exp: expiration_time.timestamp() as usize,
+        }
+    }
+}
+
+impl Claims for MagicLinkClaim {
+    const PURPOSE: &'static str = "magic_link";
+    const EXP_TIME: chrono::Duration = chrono::Duration::minutes(Self::EXPIRE_TIME_MINUTES);
+    const SUCCESS_REDIRECT_URI: &'static str = "/dashboard";
+    const COOKIE_NAME: &'static str = "magic_link";
+}
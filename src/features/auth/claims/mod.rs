@@ -1,43 +1,95 @@
 pub mod error;
 pub mod authorization;
+pub mod cookie;
+pub mod email_change;
+pub mod email_verification;
+pub mod invitation;
+pub mod keyring;
+pub mod magic_link;
+pub mod oidc;
 pub mod password_reset;
+pub mod refresh;
+pub mod two_factor;
 
-use std::sync::LazyLock;
-
-use axum::response::IntoResponse;
+use axum_extra::extract::cookie::SameSite;
 use error::ClaimsError;
-use jsonwebtoken::{DecodingKey, EncodingKey};
+use http::{header, HeaderMap};
+use serde::{de::DeserializeOwned, Serialize};
 
+use crate::utils::extract_cookie_value;
 
-pub static KEYS: LazyLock<Keys> = LazyLock::new(|| {
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    Keys::new(secret.as_bytes())
-});
 
-pub struct Keys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
+/// A signed token for one purpose. Implementors only declare the constants, every token also carries
+/// `iss`, `aud` and `typ` (the `PURPOSE`) and is refused anywhere else, see `keyring`.
+/// Claims that travel in a cookie get their extractor and response from `cookie_claim!`
+pub trait Claims: Serialize + DeserializeOwned {
+    /// The `typ` of the token, e.g. a password reset token is never accepted as a session
+    const PURPOSE: &'static str;
+    const EXP_TIME: chrono::Duration;
+    const SUCCESS_REDIRECT_URI: &'static str;
+    const COOKIE_NAME: &'static str;
+    /// Overrides the configured SameSite, for cookies that have to survive a cross site redirect
+    const SAME_SITE: Option<SameSite> = None;
 
-}
+    /// The name the cookie is sent back under, see `cookie::name`
+    fn cookie_name() -> String {
+        cookie::name(Self::COOKIE_NAME)
+    }
 
-impl Keys {
-    pub fn new(secret: &[u8]) -> Self {
-        Self {
-            encoding: EncodingKey::from_secret(secret),
-            decoding: DecodingKey::from_secret(secret),
-        }
+    /// The claim as a cookie with the configured attributes, it expires together with the token
+    fn cookie(&self) -> Result<String, ClaimsError> {
+        Ok(cookie::build(Self::COOKIE_NAME, self.token()?, Some(Self::EXP_TIME), Self::SAME_SITE))
     }
-}
 
-pub trait Claims: IntoResponse {
-    const EXP_TIME_HOURS: i64;
-    const SUCCESS_REDIRECT_URI: &'static str;
-    const COOKIE_NAME: &'static str;
+    fn removal_cookie() -> String {
+        cookie::removal(Self::COOKIE_NAME, Self::SAME_SITE)
+    }
 
-    fn cookie(&self) -> Result<String, ClaimsError>;
+    fn token(&self) -> Result<String, ClaimsError> {
+        Ok(keyring::encode(Self::PURPOSE, self)?)
+    }
+
+    fn from_token(token: String) -> Result<Self, ClaimsError> {
+        keyring::decode(Self::PURPOSE, &token).map_err(|e| {
+            tracing::debug!("Error decoding token: {:?}", e);
+            ClaimsError::InvalidToken
+        })
+    }
 
-    fn token(&self) -> Result<String, ClaimsError>;
+    /// Reads the claim from its cookie
+    fn from_cookies(headers: &HeaderMap) -> Result<Self, ClaimsError> {
+        let cookies_str = headers
+            .get(header::COOKIE)
+            .ok_or(ClaimsError::TokenNotFound)?
+            .to_str()
+            .map_err(|_| ClaimsError::InvalidToken)?;
 
-    fn from_token(token: String) -> Result<Self, ClaimsError> where Self: Sized;
+        let token = extract_cookie_value(cookies_str, &Self::cookie_name())
+            .ok_or(ClaimsError::TokenNotFound)?;
+
+        Self::from_token(token)
+    }
 }
 
+/// Extracts a `Claims` type from its cookie, and sets the cookie when it is returned from a handler
+macro_rules! cookie_claim {
+    ($ty:ty) => {
+        impl<S: Send + Sync> axum::extract::FromRequestParts<S> for $ty {
+            type Rejection = $crate::features::auth::claims::error::ClaimsError;
+
+            async fn from_request_parts(parts: &mut http::request::Parts, _state: &S) -> Result<Self, Self::Rejection> {
+                <Self as $crate::features::auth::claims::Claims>::from_cookies(&parts.headers)
+            }
+        }
+
+        impl axum::response::IntoResponse for $ty {
+            fn into_response(self) -> axum::response::Response {
+                match <Self as $crate::features::auth::claims::Claims>::cookie(&self) {
+                    Ok(cookie) => [(http::header::SET_COOKIE, cookie)].into_response(),
+                    Err(err) => err.into_response(),
+                }
+            }
+        }
+    };
+}
+pub(crate) use cookie_claim;
@@ -0,0 +1,46 @@
+use axum_extra::extract::cookie::SameSite;
+use serde::{Deserialize, Serialize};
+
+use super::{cookie_claim, Claims};
+use crate::repo::utils::generate_token;
+
+/// Remembers an authorization request between the redirect to the identity provider
+/// and its callback, binding `state`, `nonce` and the PKCE verifier to this browser
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcStateClaim {
+    pub provider: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    // where to go once signed in, already sanitized, see `return_to`
+    #[serde(default)]
+    pub next: Option<String>,
+    pub exp: usize,
+}
+
+impl OidcStateClaim {
+    pub fn new(provider: String, next: Option<String>) -> Self {
+        let expiration_time = chrono::Utc::now() + Self::EXP_TIME;
+
+        Self {
+            provider,
+            state: generate_token(32),
+            nonce: generate_token(32),
+            // rfc 7636 wants 43-128 characters
+            code_verifier: generate_token(64),
+            next,
+            exp: expiration_time.timestamp() as usize,
+        }
+    }
+}
+
+impl Claims for OidcStateClaim {
+    const PURPOSE: &'static str = "oidc_state";
+    const EXP_TIME: chrono::Duration = chrono::Duration::minutes(10);
+    const SUCCESS_REDIRECT_URI: &'static str = "/dashboard";
+    const COOKIE_NAME: &'static str = "oidc_state";
+    // the provider sends the user back with a top level cross site redirect
+    const SAME_SITE: Option<SameSite> = Some(SameSite::Lax);
+}
+
+cookie_claim!(OidcStateClaim);
@@ -0,0 +1,82 @@
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use super::{authorization::AuthorizationClaim, cookie, error::ClaimsError, Claims};
+use crate::{repo::infra::refresh_token::REFRESH_TOKEN_EXP_DAYS, utils::HxRedirect};
+
+
+/// An opaque, single-use token that is exchanged for a new `AuthorizationClaim`
+/// once the short-lived access token expires, see `RefreshTokenRepo`
+#[derive(Debug, Clone)]
+pub struct RefreshToken(pub String);
+
+impl RefreshToken {
+    pub const COOKIE_NAME: &'static str = "refresh_token";
+
+    pub fn cookie_name() -> String {
+        cookie::name(Self::COOKIE_NAME)
+    }
+
+    pub fn cookie(&self) -> String {
+        cookie::build(Self::COOKIE_NAME, self.0.clone(), Some(chrono::Duration::days(REFRESH_TOKEN_EXP_DAYS.into())), None)
+    }
+
+    pub fn removal_cookie() -> String {
+        cookie::removal(Self::COOKIE_NAME, None)
+    }
+}
+
+/// The access and refresh token pair handed out when a session starts or is refreshed
+pub struct SessionTokens {
+    pub access: AuthorizationClaim,
+    pub refresh_token: RefreshToken,
+}
+
+impl SessionTokens {
+    pub fn cookies(&self) -> Result<[String; 2], ClaimsError> {
+        Ok([self.access.cookie()?, self.refresh_token.cookie()])
+    }
+}
+
+impl SessionTokens {
+    /// For flows that arrive through a top level navigation (e.g. a provider callback)
+    /// where there is no htmx to follow `HX-Redirect`
+    pub fn redirect(self, to: &str) -> Response {
+        let [access_cookie, refresh_cookie] = match self.cookies() {
+            Ok(cookies) => cookies,
+            Err(err) => return err.into_response()
+        };
+
+        Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, to)
+            .header(header::SET_COOKIE, access_cookie)
+            .header(header::SET_COOKIE, refresh_cookie)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    /// For sign in forms submitted through htmx, `to` is usually `return_to::after_login`
+    pub fn hx_redirect(self, to: &str) -> Response {
+        let [access_cookie, refresh_cookie] = match self.cookies() {
+            Ok(cookies) => cookies,
+            Err(err) => return err.into_response()
+        };
+
+        Response::builder()
+            .header(HxRedirect::HEADER_NAME, to)
+            .header(header::SET_COOKIE, access_cookie)
+            .header(header::SET_COOKIE, refresh_cookie)
+            .body(Body::empty())
+            .unwrap()
+    }
+}
+
+impl IntoResponse for SessionTokens {
+    fn into_response(self) -> Response {
+        self.hx_redirect(AuthorizationClaim::SUCCESS_REDIRECT_URI)
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::repo::{error::RepoError, infra::two_factor_challenge::TwoFactorChallengeRepo, Repository};
+
+use super::{cookie_claim, Claims};
+
+/// Issued after a correct password for accounts with two-factor enabled,
+/// it is exchanged for an `AuthorizationClaim` once the second factor is verified
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TwoFactorClaim {
+    // the id of the server side challenge, which limits the attempts and makes it single use
+    pub jti: Uuid,
+    pub user_id: Uuid,
+    pub exp: usize,
+}
+
+impl TwoFactorClaim {
+    pub const EXPIRE_TIME_MINUTES: i64 = 5;
+
+    /// Starts a challenge for the user, replacing any they had open
+    pub async fn issue(repo: &Repository, user_id: Uuid) -> Result<Self, RepoError> {
+        let expiration_time = chrono::Utc::now() + Self::EXP_TIME;
+        let jti = Uuid::new_v4();
+
+        repo.two_factor_challenge_create(jti, user_id, Self::EXPIRE_TIME_MINUTES as i32).await?;
+
+        Ok(Self {
+            jti,
+            user_id,
+            exp: expiration_time.timestamp() as usize,
+        })
+    }
+
+}
+
+impl Claims for TwoFactorClaim {
+    const PURPOSE: &'static str = "two_factor";
+    const EXP_TIME: chrono::Duration = chrono::Duration::minutes(Self::EXPIRE_TIME_MINUTES);
+    const SUCCESS_REDIRECT_URI: &'static str = "/dashboard";
+    const COOKIE_NAME: &'static str = "two_factor";
+}
+
+cookie_claim!(TwoFactorClaim);
@@ -0,0 +1,88 @@
+//! Double submit CSRF protection. Every browser gets a random token in an http only cookie,
+//! pages render the same token into `hx-headers` so htmx sends it back as `X-CSRF-Token`,
+//! and unsafe requests are refused unless the two match. Another site can make the browser
+//! send the cookie but cannot read the page to learn the header value.
+
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header, HeaderValue, Method, StatusCode};
+
+use crate::{features::auth::claims::cookie, repo::utils::generate_token, utils::{bearer_token, extract_cookie_value}};
+
+
+pub const CSRF_COOKIE_NAME: &str = "csrf_token";
+pub const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+const CSRF_TOKEN_LENGTH: usize = 32;
+
+tokio::task_local! {
+    static CSRF_TOKEN: String;
+}
+
+/// The token of the request being handled, empty outside of `csrf_protect`
+pub fn token() -> String {
+    CSRF_TOKEN.try_with(|token| token.clone()).unwrap_or_default()
+}
+
+/// The value for `hx-headers`, e.g. on `<body>` so every htmx request carries the token
+pub fn hx_headers() -> String {
+    format!(r#"{{"{}": "{}"}}"#, CSRF_HEADER_NAME, token())
+}
+
+pub fn is_unsafe(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+}
+
+// api clients authenticating with a bearer token dont rely on ambient cookies, and another
+// site cannot attach an authorization header to a request without passing cors first
+fn is_exempt(request: &Request) -> bool {
+    bearer_token(request.headers()).is_some()
+}
+
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub async fn csrf_protect(request: Request, next: Next) -> Response {
+    let cookie_token = request
+        .headers()
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|cookies| extract_cookie_value(cookies, &cookie::name(CSRF_COOKIE_NAME)));
+
+    if is_unsafe(request.method()) && !is_exempt(&request) {
+        let header_token = request
+            .headers()
+            .get(CSRF_HEADER_NAME)
+            .and_then(|v| v.to_str().ok());
+
+        let valid = match (&cookie_token, header_token) {
+            (Some(cookie_token), Some(header_token)) => tokens_match(cookie_token, header_token),
+            _ => false,
+        };
+
+        if !valid {
+            tracing::debug!("Rejected {} {} without a valid csrf token", request.method(), request.uri().path());
+            return (StatusCode::FORBIDDEN, "This page expired, please reload it and try again").into_response();
+        }
+    }
+
+    let (token, is_new) = match cookie_token {
+        Some(token) => (token, false),
+        None => (generate_token(CSRF_TOKEN_LENGTH), true),
+    };
+
+    let mut response = CSRF_TOKEN.scope(token.clone(), next.run(request)).await;
+
+    if is_new {
+        let cookie = cookie::build(CSRF_COOKIE_NAME, token, None, None);
+
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+
+    response
+}
@@ -0,0 +1,79 @@
+//! The signed in user loaded from the database. Use `CurrentUser` when a handler needs more than
+//! the ids in the claim, `Option<CurrentUser>` or `MaybeUser` for pages anyone may see.
+
+use axum::extract::{FromRef, FromRequestParts, OptionalFromRequestParts};
+use http::request::Parts;
+
+use crate::{repo::infra::user::{User, UserRepo}, web_service::server::ServerState};
+
+use super::claims::{authorization::AuthorizationClaim, error::ClaimsError};
+
+
+/// Resolved from the session cookie or a personal access token, once per request.
+/// Later extractions (e.g. a layer and then the handler) reuse it.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub claim: AuthorizationClaim,
+    pub user: User,
+}
+
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    ServerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ClaimsError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(current_user) = parts.extensions.get::<CurrentUser>() {
+            return Ok(current_user.clone());
+        }
+
+        let claim = AuthorizationClaim::from_request_parts(parts, state).await?;
+
+        let state = ServerState::from_ref(state);
+        let user = state.repo
+            .user_get_by_id(claim.user_id)
+            .await?
+            .ok_or_else(|| ClaimsError::UserNotFound.login_required(parts))?;
+
+        let current_user = CurrentUser { claim, user };
+        parts.extensions.insert(current_user.clone());
+
+        Ok(current_user)
+    }
+}
+
+// visitors without a valid token are just anonymous, only a failing database is an error
+impl<S> OptionalFromRequestParts<S> for CurrentUser
+where
+    ServerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ClaimsError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        match <Self as FromRequestParts<S>>::from_request_parts(parts, state).await {
+            Ok(current_user) => Ok(Some(current_user)),
+            Err(ClaimsError::RepoError(err)) => Err(ClaimsError::RepoError(err)),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// `Option<CurrentUser>` for handlers that read better with a named type
+#[derive(Debug, Clone)]
+pub struct MaybeUser(pub Option<CurrentUser>);
+
+impl<S> FromRequestParts<S> for MaybeUser
+where
+    ServerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ClaimsError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let current_user = <CurrentUser as OptionalFromRequestParts<S>>::from_request_parts(parts, state).await?;
+        Ok(Self(current_user))
+    }
+}
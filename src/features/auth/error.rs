@@ -1,5 +1,6 @@
-use axum::response::{IntoResponse, Response};
-use http::StatusCode;
+use askama::Template;
+use axum::response::{Html, IntoResponse, Response};
+use http::{header::RETRY_AFTER, StatusCode};
 
 use crate::repo::error::RepoError;
 
@@ -14,6 +15,23 @@ pub enum AuthError {
     EmailAlreadyExists,
     WrongPassword,
     PasswordsDontMatch,
+    InvalidTwoFactorCode,
+    // the sign in waiting for a second factor expired, was finished or ran out of attempts
+    TwoFactorChallengeExpired,
+    // the passkey ceremony expired, was already finished or belongs to someone else
+    PasskeyCeremonyNotFound,
+    PasskeyNotFound,
+    // the sign in link is invalid, expired or was already used
+    MagicLinkInvalid,
+    VerificationLinkInvalid,
+    // the change expired, was already applied or the email changed in the meantime
+    EmailChangeInvalid,
+    // too many wrong codes were entered or the reset expired
+    PasswordResetExpired,
+    // a personal access token needs a name, a scope and an expiry of at most a year or none at all
+    AccessTokenSettingsInvalid,
+    // the account or ip failed to log in too often and is locked for a while
+    TooManyAttempts { retry_after_seconds: u64 },
     #[from]
     ClaimsError(ClaimsError),
 
@@ -25,6 +43,21 @@ pub enum AuthError {
 
     #[from]
     Mailer(crate::mailer::error::MailerError),
+
+    #[from]
+    Totp(totp_rs::TotpError),
+
+    #[from]
+    TotpSecret(totp_rs::SecretParseError),
+
+    #[from]
+    Webauthn(webauthn_rs::prelude::WebauthnError),
+
+    #[from]
+    Json(serde_json::Error),
+
+    #[from]
+    Oidc(super::oidc::error::OidcError),
 }
 
 impl IntoResponse for AuthError {
@@ -43,6 +76,10 @@ impl IntoResponse for AuthError {
             AuthError::ClaimsError(err) => {
                 err.into_response()
             },
+
+            AuthError::Oidc(err) => {
+                err.into_response()
+            },
             
             AuthError::RepoError(err) => {
                 err.into_response()
@@ -52,6 +89,59 @@ impl IntoResponse for AuthError {
                 (StatusCode::UNAUTHORIZED, "Wrong password").into_response()
             },
 
+            AuthError::InvalidTwoFactorCode => {
+                (StatusCode::UNAUTHORIZED, "Invalid code, please try again").into_response()
+            },
+
+            AuthError::TwoFactorChallengeExpired => {
+                (StatusCode::UNAUTHORIZED, "This sign in expired or had too many wrong codes, please sign in again").into_response()
+            },
+
+            AuthError::PasskeyCeremonyNotFound => {
+                (StatusCode::BAD_REQUEST, "This passkey request expired, please try again").into_response()
+            },
+
+            AuthError::PasskeyNotFound => {
+                (StatusCode::UNAUTHORIZED, "This passkey is not registered").into_response()
+            },
+
+            AuthError::MagicLinkInvalid => {
+                (StatusCode::UNAUTHORIZED, "This sign in link expired or was already used, request a new one").into_response()
+            },
+
+            AuthError::VerificationLinkInvalid => {
+                (StatusCode::BAD_REQUEST, "This verification link is invalid or expired, request a new one from your dashboard").into_response()
+            },
+
+            AuthError::EmailChangeInvalid => {
+                (StatusCode::BAD_REQUEST, "This email change link is invalid or expired").into_response()
+            },
+
+            AuthError::PasswordResetExpired => {
+                (StatusCode::UNAUTHORIZED, "This code is no longer valid, please request a new one").into_response()
+            },
+
+            AuthError::AccessTokenSettingsInvalid => {
+                (StatusCode::BAD_REQUEST, "Give the token a name, at least one scope and an expiry of at most a year, or let it never expire").into_response()
+            },
+
+            AuthError::TooManyAttempts { retry_after_seconds } => {
+                let body = super::handlers::views::authentication::TooManyAttempts::new(retry_after_seconds)
+                    .render()
+                    .unwrap_or_else(|_| "Too many failed sign in attempts, please try again later".to_string());
+
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(RETRY_AFTER, retry_after_seconds.to_string())],
+                    Html(body),
+                ).into_response()
+            },
+
+            AuthError::Webauthn(err) => {
+                tracing::debug!("Passkey verification failed: {:?}", err);
+                (StatusCode::UNAUTHORIZED, "Passkey could not be verified").into_response()
+            },
+
             err => {
                 tracing::error!("A auth error occured: {:?}", err);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error, please try again later.").into_response()
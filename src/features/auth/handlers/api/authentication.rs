@@ -1,14 +1,29 @@
-use axum::{extract::{Form, State}, response::IntoResponse};
+use askama::Template;
+use axum::{extract::{Form, State}, response::{AppendHeaders, Html, IntoResponse, Response}};
 use http::StatusCode;
-use crate::{features::auth::error::AuthError, repo::infra::user::UserRepo, utils::HxRedirect, web_service::server::ServerState};
+use crate::{features::auth::{claims::{error::ClaimsError, two_factor::TwoFactorClaim, Claims}, error::AuthError, handlers::views::two_factor::TwoFactorForm}, repo::infra::{session::SessionRepo, totp::TotpRepo, user::UserRepo}, utils::{ClientInfo, HxRedirect}, web_service::server::ServerState};
 
 
-use crate::features::auth::claims::authorization::AuthorizationClaim;
+use crate::features::auth::claims::{authorization::AuthorizationClaim, refresh::RefreshToken};
+use crate::features::auth::handlers::api::email_verification::{send_verification_email, verification_email};
+use crate::features::auth::handlers::views::authentication::RegistrationPending;
+use crate::features::auth::{privacy, return_to, throttle};
+use crate::repo::{error::RepoError, utils::{is_valid_email, is_valid_password}};
+use tokio::time::Instant;
 
 
-pub async fn logout() -> Result<impl IntoResponse, AuthError> {
+pub async fn logout(State(state): State<ServerState>, claim: Result<AuthorizationClaim, ClaimsError>) -> Result<impl IntoResponse, AuthError> {
+    // an invalid or already revoked token still gets its cookie cleared
+    if let Ok(AuthorizationClaim { session_id: Some(session_id), .. }) = claim {
+        state.repo.session_revoke(session_id).await?;
+    }
+
     Ok((
-        [(http::header::SET_COOKIE, "token=; Path=/; HttpOnly")],
+        // a plain array would keep only the last Set-Cookie
+        AppendHeaders([
+            (http::header::SET_COOKIE, AuthorizationClaim::removal_cookie()),
+            (http::header::SET_COOKIE, RefreshToken::removal_cookie()),
+        ]),
         [(HxRedirect::HEADER_NAME, "/")],
         StatusCode::OK,
     ))
@@ -21,20 +36,74 @@ pub struct RegisterPayload {
     pub password: String,
 }
 
-pub async fn register(State(state): State<ServerState>, Form(user_data): Form<RegisterPayload>) -> Result<impl IntoResponse, AuthError> {
+#[derive(Template)]
+#[template(path = "auth/emails/account_exists.html")]
+pub struct AccountExistsEmailTemplate {
+    login_link: String,
+    reset_link: String,
+}
+
+pub async fn register(State(state): State<ServerState>, client: ClientInfo, Form(user_data): Form<RegisterPayload>) -> Result<Response, AuthError> {
+    if state.config.app.privacy_mode {
+        return register_privately(state, user_data).await;
+    }
+
     let user_res = state.repo.user_get_by_email(&user_data.email).await?; 
     if user_res.is_some() {
         tracing::debug!("User already exists");
-        return Err(AuthError::EmailAlreadyExists)
+        Err(AuthError::EmailAlreadyExists)
     } else {
         tracing::debug!("User does not exist, creating user...");
 
         let id = state.repo.user_create(&user_data.email, &user_data.password, &user_data.name).await?;
 
-        let claims = AuthorizationClaim::new(id);
+        // a failed email should not fail the sign up, the dashboard can resend it
+        if let Some(user) = state.repo.user_get_by_id(id).await?
+            && let Err(err) = send_verification_email(&state, &user).await
+        {
+            tracing::error!("Could not send verification email: {:?}", err);
+        }
+
+        let claims = AuthorizationClaim::start_session(&state.repo, id, &client).await?;
+
+        Ok(claims.into_response())
+    }
+}
+
+// nobody is signed in here, new and existing accounts alike are only told to check their inbox
+async fn register_privately(state: ServerState, user_data: RegisterPayload) -> Result<Response, AuthError> {
+    let started = Instant::now();
+
+    // validated up front, failing only for new emails would give them away
+    is_valid_email(&user_data.email)?;
+    is_valid_password(&user_data.password)?;
+
+    match state.repo.user_get_by_email(&user_data.email).await? {
+        Some(user) => {
+            privacy::dummy_password_hash(&user_data.password);
 
-        Ok(claims)
+            let origin = state.config.app.origin.trim_end_matches('/');
+            let body = AccountExistsEmailTemplate {
+                login_link: format!("{}/auth/login", origin),
+                reset_link: format!("{}/auth/reset-password", origin),
+            }
+            .render()?;
+
+            let message = state.mailer.create_message(body, user.email, user.name, "You already have an account".to_string())?;
+            state.mailer.send_in_background(message);
+        },
+        None => {
+            let id = state.repo.user_create(&user_data.email, &user_data.password, &user_data.name).await?;
+
+            if let Some(user) = state.repo.user_get_by_id(id).await? {
+                state.mailer.send_in_background(verification_email(&state, &user)?);
+            }
+        },
     }
+
+    privacy::uniform_delay(started).await;
+
+    Ok(Html(RegistrationPending { email: user_data.email }.render()?).into_response())
 }   
 
 
@@ -43,18 +112,56 @@ pub async fn register(State(state): State<ServerState>, Form(user_data): Form<Re
 pub struct LoginPayload {
     pub email: String,
     pub password: String,
+    // the page that sent the visitor to sign in, see `return_to`
+    pub next: Option<String>,
 }
 
-pub async fn login(State(state): State<ServerState>, Form(user_data): Form<LoginPayload>) -> Result<impl IntoResponse, AuthError> {
+pub async fn login(State(state): State<ServerState>, client: ClientInfo, Form(user_data): Form<LoginPayload>) -> Result<impl IntoResponse, AuthError> {
     // is_valid_email(&user_data.email)?;
 
-    let password_check = state.repo.user_check_password(&user_data.email, &user_data.password).await?;
+    let throttle_key = throttle::key(&user_data.email);
+    let ip = client.ip.as_deref();
+    throttle::check(&state, &throttle_key, ip).await?;
+
+    let password_check = match state.repo.user_check_password(&user_data.email, &user_data.password).await {
+        Err(RepoError::EmailNotFound) => {
+            throttle::record_failure(&state, &throttle_key).await?;
+
+            // looks and takes the same as a wrong password for an existing account
+            if state.config.app.privacy_mode {
+                privacy::dummy_password_hash(&user_data.password);
+                return Err(AuthError::WrongPassword);
+            }
+            return Err(RepoError::EmailNotFound.into());
+        },
+        res => res?,
+    };
+
     match password_check {
         Some(id) => {
-            let claims = AuthorizationClaim::new(id);
-            Ok(claims)
+            // with two-factor enabled the session only starts after the code is verified,
+            // until then the attempt hasn't succeeded and the earlier failures keep counting
+            let two_factor = state.repo.totp_get(id).await?;
+            if two_factor.is_some_and(|t| t.is_enabled()) {
+                throttle::release(&state, &throttle_key, ip).await?;
+                let claim = TwoFactorClaim::issue(&state.repo, id).await?;
+                let body = TwoFactorForm { next: return_to::sanitize(user_data.next.as_deref()) }.render()?;
+
+                return Ok((
+                    [(http::header::SET_COOKIE, claim.cookie()?)],
+                    Html(body),
+                ).into_response());
+            }
+
+            throttle::record_success(&state, &throttle_key, ip).await?;
+
+            let claims = AuthorizationClaim::start_session(&state.repo, id, &client).await?;
+            Ok(claims.hx_redirect(&return_to::after_login(user_data.next.as_deref())))
         },
-        None => Err(AuthError::WrongPassword)
+        None => {
+            throttle::record_failure(&state, &throttle_key).await?;
+            Err(AuthError::WrongPassword)
+        }
     }
 }
 
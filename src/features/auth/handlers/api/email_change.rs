@@ -0,0 +1,101 @@
+use askama::Template;
+use axum::{extract::{Query, State}, response::{Html, IntoResponse, Redirect}, Form};
+use serde::Deserialize;
+
+use crate::{
+    features::auth::{
+        claims::{authorization::AuthorizationClaim, email_change::EmailChangeClaim, Claims},
+        current_user::CurrentUser,
+        error::AuthError,
+        handlers::views::email_change::EmailChangeSent,
+    },
+    repo::infra::{email_change::{EmailChangeOutcome, EmailChangeRepo}, user::UserRepo},
+    web_service::server::ServerState,
+};
+
+
+#[derive(Template)]
+#[template(path = "auth/emails/email_change_confirm.html")]
+pub struct EmailChangeConfirmTemplate {
+    link: String,
+    expire_time: String,
+}
+
+#[derive(Template)]
+#[template(path = "auth/emails/email_change_notice.html")]
+pub struct EmailChangeNoticeTemplate {
+    new_email: String,
+    link: String,
+    expire_time: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailPayload {
+    new_email: String,
+}
+
+pub async fn request(State(state): State<ServerState>, CurrentUser { user, .. }: CurrentUser, Form(payload): Form<ChangeEmailPayload>) -> Result<impl IntoResponse, AuthError> {
+
+    let new_email = payload.new_email.trim().to_string();
+
+    if state.repo.user_get_by_email(&new_email).await?.is_some() {
+        return Err(AuthError::EmailAlreadyExists);
+    }
+
+    let change_id = state.repo.email_change_create(user.id, &user.email, &new_email).await?;
+    let origin = state.config.app.origin.trim_end_matches('/');
+
+    // the old address is told first so a hijacked session cannot move the account quietly
+    let body = EmailChangeNoticeTemplate {
+        new_email: new_email.clone(),
+        link: format!("{}/api/auth/change-email/revert?token={}", origin, EmailChangeClaim::revert(change_id).token()?),
+        expire_time: EmailChangeClaim::REVERT_EXPIRE_TIME_DAYS.to_string(),
+    }
+    .render()?;
+    let notice = state.mailer.create_message(body, user.email, user.name.clone(), "Your email is being changed".to_string())?;
+
+    let body = EmailChangeConfirmTemplate {
+        link: format!("{}/api/auth/change-email/confirm?token={}", origin, EmailChangeClaim::confirm(change_id).token()?),
+        expire_time: EmailChangeClaim::EXPIRE_TIME_HOURS.to_string(),
+    }
+    .render()?;
+    let confirm = state.mailer.create_message(body, new_email.clone(), user.name, "Confirm your new email".to_string())?;
+
+    // both are built before either goes out, so the request cannot fail once the confirm link is sent
+    state.mailer.send_in_background(notice);
+    state.mailer.send_in_background(confirm);
+
+    Ok(Html(EmailChangeSent { new_email }.render()?))
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: String,
+}
+
+pub async fn confirm(State(state): State<ServerState>, Query(query): Query<TokenQuery>) -> Result<impl IntoResponse, AuthError> {
+    let claim = EmailChangeClaim::from_token(query.token)
+        .ok()
+        .filter(|claim| claim.is_confirm())
+        .ok_or(AuthError::EmailChangeInvalid)?;
+
+    match state.repo.email_change_confirm(claim.change_id).await? {
+        EmailChangeOutcome::Changed { .. } => Ok(Redirect::to(AuthorizationClaim::SUCCESS_REDIRECT_URI)),
+        EmailChangeOutcome::EmailTaken => Err(AuthError::EmailAlreadyExists),
+        EmailChangeOutcome::Invalid => Err(AuthError::EmailChangeInvalid),
+    }
+}
+
+pub async fn revert(State(state): State<ServerState>, Query(query): Query<TokenQuery>) -> Result<impl IntoResponse, AuthError> {
+    let claim = EmailChangeClaim::from_token(query.token)
+        .ok()
+        .filter(|claim| claim.is_revert())
+        .ok_or(AuthError::EmailChangeInvalid)?;
+
+    match state.repo.email_change_revert(claim.change_id).await? {
+        // whoever made the change is signed out already, the owner resets the password next
+        EmailChangeOutcome::Changed { .. } => Ok(Redirect::to("/auth/reset-password")),
+        EmailChangeOutcome::EmailTaken => Err(AuthError::EmailAlreadyExists),
+        EmailChangeOutcome::Invalid => Err(AuthError::EmailChangeInvalid),
+    }
+}
@@ -0,0 +1,82 @@
+use askama::Template;
+use axum::{extract::{Query, State}, response::{Html, IntoResponse, Redirect}};
+use lettre::Message;
+use serde::Deserialize;
+
+use crate::{
+    features::auth::{
+        claims::{authorization::AuthorizationClaim, email_verification::EmailVerificationClaim, Claims},
+        current_user::CurrentUser,
+        error::AuthError,
+        handlers::views::email_verification::VerificationSent,
+    },
+    repo::infra::user::{User, UserRepo},
+    web_service::server::ServerState,
+};
+
+
+#[derive(Template)]
+#[template(path = "auth/emails/verify_email.html")]
+pub struct VerifyEmailTemplate {
+    link: String,
+    expire_time: String,
+}
+
+pub async fn send_verification_email(state: &ServerState, user: &User) -> Result<(), AuthError> {
+    let message = verification_email(state, user)?;
+    state.mailer.send_message(message)?;
+
+    Ok(())
+}
+
+pub fn verification_email(state: &ServerState, user: &User) -> Result<Message, AuthError> {
+    let claim = EmailVerificationClaim::new(user.id, user.email.clone());
+
+    let link = format!(
+        "{}/api/auth/verify-email?token={}",
+        state.config.app.origin.trim_end_matches('/'),
+        claim.token()?
+    );
+
+    let body = VerifyEmailTemplate {
+        link,
+        expire_time: EmailVerificationClaim::EXPIRE_TIME_HOURS.to_string(),
+    }
+    .render()?;
+
+    let message = state.mailer.create_message(body, user.email.clone(), user.name.clone(), "Verify your email".to_string())?;
+
+    Ok(message)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyQuery {
+    token: String,
+}
+
+// verifying twice is harmless, so unlike sign in links this works straight from the email
+pub async fn verify(State(state): State<ServerState>, Query(query): Query<VerifyQuery>) -> Result<impl IntoResponse, AuthError> {
+    let claim = EmailVerificationClaim::from_token(query.token).map_err(|_| AuthError::VerificationLinkInvalid)?;
+
+    let user = state.repo
+        .user_get_by_id(claim.user_id)
+        .await?
+        .filter(|user| user.email == claim.email)
+        .ok_or(AuthError::VerificationLinkInvalid)?;
+
+    state.repo.user_mark_email_verified(user.id).await?;
+
+    Ok(Redirect::to(AuthorizationClaim::SUCCESS_REDIRECT_URI))
+}
+
+pub async fn resend(State(state): State<ServerState>, CurrentUser { user, .. }: CurrentUser) -> Result<impl IntoResponse, AuthError> {
+    if !user.is_email_verified() {
+        send_verification_email(&state, &user).await?;
+    }
+
+    Ok(Html(VerificationSent {
+        email: user.email,
+        already_verified: user.email_verified_at.is_some(),
+    }
+    .render()?))
+}
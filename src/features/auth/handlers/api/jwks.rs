@@ -0,0 +1,15 @@
+use axum::{response::IntoResponse, Json};
+use http::header;
+
+use crate::features::auth::claims::keyring::keyring;
+
+
+/// The public keys our tokens can be verified with, see `keyring`.
+/// Shared secrets are left out, tokens signed with them can only be checked by us
+pub async fn jwks() -> impl IntoResponse {
+    (
+        // short enough that a rotated key shows up before tokens signed with it are common
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(keyring().jwks()),
+    )
+}
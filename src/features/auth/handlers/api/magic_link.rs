@@ -0,0 +1,110 @@
+use askama::Template;
+use axum::{extract::State, response::{Html, IntoResponse}, Form};
+use http::header::SET_COOKIE;
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use crate::{
+    features::auth::{
+        claims::{authorization::AuthorizationClaim, magic_link::MagicLinkClaim, two_factor::TwoFactorClaim, Claims},
+        error::AuthError,
+        privacy,
+        return_to,
+        handlers::views::{magic_link::MagicLinkSent, two_factor::TwoFactorForm},
+    },
+    repo::infra::{magic_link::MagicLinkRepo, totp::TotpRepo, user::UserRepo},
+    utils::ClientInfo,
+    web_service::server::ServerState,
+};
+
+
+#[derive(Template)]
+#[template(path = "auth/emails/magic_link.html")]
+pub struct MagicLinkEmailTemplate {
+    link: String,
+    expire_time: String,
+}
+
+#[derive(Deserialize)]
+pub struct EmailPayload {
+    email: String,
+    // where to go once signed in, see `return_to`
+    next: Option<String>,
+}
+
+pub async fn request(State(state): State<ServerState>, Form(payload): Form<EmailPayload>) -> Result<impl IntoResponse, AuthError> {
+    let started = Instant::now();
+    let privacy_mode = state.config.app.privacy_mode;
+
+    let Some(user) = state.repo.user_get_by_email(payload.email.as_str()).await? else {
+        if !privacy_mode {
+            return Err(AuthError::EmailNotFound);
+        }
+
+        privacy::uniform_delay(started).await;
+        return Ok(Html(MagicLinkSent { email: payload.email }.render()?));
+    };
+
+    let claim = MagicLinkClaim::new(user.id, return_to::sanitize(payload.next.as_deref()));
+    state.repo
+        .magic_link_create(claim.jti, user.id, MagicLinkClaim::EXPIRE_TIME_MINUTES as i32)
+        .await?;
+
+    let link = format!(
+        "{}/auth/magic-link?token={}",
+        state.config.app.origin.trim_end_matches('/'),
+        claim.token()?
+    );
+
+    let body = MagicLinkEmailTemplate {
+        link,
+        expire_time: MagicLinkClaim::EXPIRE_TIME_MINUTES.to_string(),
+    }
+    .render()?;
+
+    let message = state.mailer.create_message(body, user.email.clone(), user.name, "Your sign in link".to_string())?;
+
+    if privacy_mode {
+        state.mailer.send_in_background(message);
+        privacy::uniform_delay(started).await;
+    } else {
+        state.mailer.send_message(message)?;
+    }
+
+    Ok(Html(MagicLinkSent { email: user.email }.render()?))
+}
+
+#[derive(Deserialize)]
+pub struct VerifyPayload {
+    token: String,
+}
+
+// posted from the page the link opens, so mail scanners following the link dont use it up
+pub async fn verify(State(state): State<ServerState>, client: ClientInfo, Form(payload): Form<VerifyPayload>) -> Result<impl IntoResponse, AuthError> {
+    let claim = MagicLinkClaim::from_token(payload.token).map_err(|_| AuthError::MagicLinkInvalid)?;
+
+    let user_id = state.repo
+        .magic_link_use(claim.jti)
+        .await?
+        .filter(|user_id| *user_id == claim.user_id)
+        .ok_or(AuthError::MagicLinkInvalid)?;
+
+    // the link was delivered to the inbox, which is all verification proves
+    state.repo.user_mark_email_verified(user_id).await?;
+
+    // the link only proves access to the inbox, two-factor still applies
+    let two_factor = state.repo.totp_get(user_id).await?;
+    if two_factor.is_some_and(|t| t.is_enabled()) {
+        let two_factor_claim = TwoFactorClaim::issue(&state.repo, user_id).await?;
+        let body = TwoFactorForm { next: claim.next }.render()?;
+
+        return Ok((
+            [(SET_COOKIE, two_factor_claim.cookie()?)],
+            Html(body),
+        ).into_response());
+    }
+
+    let tokens = AuthorizationClaim::start_session(&state.repo, user_id, &client).await?;
+
+    Ok(tokens.hx_redirect(&return_to::after_login(claim.next.as_deref())))
+}
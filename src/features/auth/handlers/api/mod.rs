@@ -1,5 +1,10 @@
 pub mod authentication;
+pub mod email_change;
+pub mod email_verification;
+pub mod jwks;
+pub mod magic_link;
+pub mod oidc;
+pub mod passkey;
 pub mod password_reset;
-
-
-
+pub mod personal_access_token;
+pub mod two_factor;
@@ -0,0 +1,129 @@
+use axum::{extract::{Path, Query, State}, response::{AppendHeaders, IntoResponse, Redirect, Response}};
+use http::header::SET_COOKIE;
+use serde::Deserialize;
+
+use crate::{
+    features::auth::{
+        claims::{authorization::AuthorizationClaim, oidc::OidcStateClaim, two_factor::TwoFactorClaim, Claims},
+        error::AuthError,
+        oidc::{self, error::OidcError, IdTokenClaims},
+        return_to::{self, NextQuery},
+    },
+    repo::infra::{identity::IdentityRepo, totp::TotpRepo, user::UserRepo},
+    utils::ClientInfo,
+    web_service::server::ServerState,
+};
+
+
+pub async fn start(State(state): State<ServerState>, Path(provider_id): Path<String>, Query(query): Query<NextQuery>) -> Result<impl IntoResponse, AuthError> {
+    let provider = state.config.oidc
+        .get(&provider_id)
+        .ok_or(OidcError::UnknownProvider)?;
+
+    let metadata = state.oidc.discover(provider).await?;
+    let redirect_uri = oidc::redirect_uri(&state.config.app.origin, &provider_id);
+
+    let claim = OidcStateClaim::new(provider_id, return_to::sanitize(query.next.as_deref()));
+    let url = oidc::authorization_url(&metadata, provider, &redirect_uri, &claim)?;
+
+    Ok((
+        AppendHeaders([(SET_COOKIE, claim.cookie()?)]),
+        Redirect::to(url.as_str()),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+pub async fn callback(
+    State(state): State<ServerState>,
+    client: ClientInfo,
+    Path(provider_id): Path<String>,
+    claim: OidcStateClaim,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Response, AuthError> {
+    if let Some(error) = query.error {
+        return Err(OidcError::ProviderError(error).into());
+    }
+
+    if claim.provider != provider_id || query.state.as_deref() != Some(claim.state.as_str()) {
+        return Err(OidcError::StateMismatch.into());
+    }
+
+    let code = query.code.ok_or(OidcError::StateMismatch)?;
+
+    let provider = state.config.oidc
+        .get(&provider_id)
+        .ok_or(OidcError::UnknownProvider)?;
+
+    let metadata = state.oidc.discover(provider).await?;
+    let redirect_uri = oidc::redirect_uri(&state.config.app.origin, &provider_id);
+    let identity = state.oidc.exchange_code(&metadata, provider, &redirect_uri, &code, &claim).await?;
+
+    let user_id = resolve_user(&state, &provider_id, identity).await?;
+
+    // signing in through a provider does not skip the user's own second factor
+    let two_factor_enabled = state.repo
+        .totp_get(user_id)
+        .await?
+        .is_some_and(|t| t.is_enabled());
+
+    if two_factor_enabled {
+        let claim_next = claim.next;
+        let claim = TwoFactorClaim::issue(&state.repo, user_id).await?;
+
+        return Ok((
+            AppendHeaders([
+                (SET_COOKIE, OidcStateClaim::removal_cookie()),
+                (SET_COOKIE, claim.cookie()?),
+            ]),
+            Redirect::to(&return_to::two_factor_url(claim_next.as_deref())),
+        ).into_response());
+    }
+
+    let tokens = AuthorizationClaim::start_session(&state.repo, user_id, &client).await?;
+
+    Ok((
+        AppendHeaders([(SET_COOKIE, OidcStateClaim::removal_cookie())]),
+        tokens.redirect(&return_to::after_login(claim.next.as_deref())),
+    ).into_response())
+}
+
+// finds the account linked to this identity, linking or creating one on first sign in
+async fn resolve_user(state: &ServerState, provider_id: &str, identity: IdTokenClaims) -> Result<uuid::Uuid, AuthError> {
+    if let Some(user_id) = state.repo.identity_get_user_id(provider_id, &identity.sub).await? {
+        return Ok(user_id);
+    }
+
+    // an unverified email could belong to someone else, so it is never used to match accounts
+    let email = identity.email
+        .filter(|_| identity.email_verified)
+        .ok_or(OidcError::EmailNotVerified)?;
+
+    let user_id = match state.repo.user_get_by_email(&email).await? {
+        Some(user) => {
+            // whoever registered an unverified account never proved they own the email,
+            // so their password, passkeys, second factor and sessions dont survive the real owner signing in
+            if !user.is_email_verified() {
+                state.repo.identity_take_over_unverified(user.id).await?;
+            }
+            user.id
+        },
+        None => {
+            let name = identity.name
+                .filter(|n| !n.trim().is_empty())
+                .unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_string());
+            let name: String = name.trim().chars().take(100).collect();
+
+            state.repo.user_create_passwordless(&email, &name).await?
+        }
+    };
+
+    state.repo.identity_link(provider_id, &identity.sub, user_id, Some(&email)).await?;
+
+    Ok(user_id)
+}
@@ -0,0 +1,154 @@
+use askama::Template;
+use axum::{extract::{Path, State}, response::{Html, IntoResponse}, Json};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, DiscoverableAuthentication, DiscoverableKey, Passkey,
+    PasskeyRegistration, PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse,
+};
+
+use crate::{
+    features::auth::{
+        claims::authorization::AuthorizationClaim,
+        current_user::CurrentUser,
+        error::AuthError,
+        return_to,
+        handlers::views::passkey::{PasskeyRow, PasskeysFragment},
+    },
+    repo::infra::webauthn::WebauthnRepo,
+    utils::ClientInfo,
+    web_service::server::ServerState,
+};
+
+
+async fn render_passkeys(state: &ServerState, user_id: Uuid) -> Result<Html<String>, AuthError> {
+    let passkeys = state.repo
+        .webauthn_credentials_for_user(user_id)
+        .await?
+        .into_iter()
+        .map(PasskeyRow::from)
+        .collect();
+
+    Ok(Html(PasskeysFragment { passkeys }.render()?))
+}
+
+#[derive(Serialize)]
+pub struct RegisterStartResponse {
+    ceremony_id: Uuid,
+    options: CreationChallengeResponse,
+}
+
+pub async fn register_start(State(state): State<ServerState>, CurrentUser { user, .. }: CurrentUser) -> Result<impl IntoResponse, AuthError> {
+
+    // dont let the same authenticator register twice
+    let existing = state.repo
+        .webauthn_credentials_for_user(user.id)
+        .await?
+        .into_iter()
+        .map(|c| serde_json::from_value::<Passkey>(c.passkey).map(|p| p.cred_id().clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the user handle stored on the authenticator is the user id, passkey login relies on it
+    let (options, registration) = state.webauthn.start_passkey_registration(user.id, &user.email, &user.name, Some(existing))?;
+
+    let ceremony_id = state.repo
+        .webauthn_ceremony_create(Some(user.id), serde_json::to_value(&registration)?)
+        .await?;
+
+    Ok(Json(RegisterStartResponse { ceremony_id, options }))
+}
+
+#[derive(Deserialize)]
+pub struct RegisterFinishPayload {
+    ceremony_id: Uuid,
+    name: Option<String>,
+    credential: RegisterPublicKeyCredential,
+}
+
+pub async fn register_finish(State(state): State<ServerState>, claim: AuthorizationClaim, Json(payload): Json<RegisterFinishPayload>) -> Result<impl IntoResponse, AuthError> {
+    let ceremony = state.repo
+        .webauthn_ceremony_take(payload.ceremony_id)
+        .await?
+        .filter(|c| c.user_id == Some(claim.user_id))
+        .ok_or(AuthError::PasskeyCeremonyNotFound)?;
+
+    let registration: PasskeyRegistration = serde_json::from_value(ceremony.state)?;
+    let passkey = state.webauthn.finish_passkey_registration(&payload.credential, &registration)?;
+
+    let name = payload.name
+        .map(|n| n.trim().chars().take(100).collect::<String>())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Passkey".to_string());
+
+    state.repo
+        .webauthn_credential_create(claim.user_id, passkey.cred_id().as_ref(), serde_json::to_value(&passkey)?, &name)
+        .await?;
+
+    render_passkeys(&state, claim.user_id).await
+}
+
+#[derive(Serialize)]
+pub struct LoginStartResponse {
+    ceremony_id: Uuid,
+    options: RequestChallengeResponse,
+}
+
+// usernameless, the authenticator tells us which user the passkey belongs to
+pub async fn login_start(State(state): State<ServerState>) -> Result<impl IntoResponse, AuthError> {
+    let (options, authentication) = state.webauthn.start_discoverable_authentication()?;
+
+    let ceremony_id = state.repo
+        .webauthn_ceremony_create(None, serde_json::to_value(&authentication)?)
+        .await?;
+
+    Ok(Json(LoginStartResponse { ceremony_id, options }))
+}
+
+#[derive(Deserialize)]
+pub struct LoginFinishPayload {
+    ceremony_id: Uuid,
+    credential: PublicKeyCredential,
+    // the login page's `next`, see `return_to`
+    next: Option<String>,
+}
+
+pub async fn login_finish(State(state): State<ServerState>, client: ClientInfo, Json(payload): Json<LoginFinishPayload>) -> Result<impl IntoResponse, AuthError> {
+    let ceremony = state.repo
+        .webauthn_ceremony_take(payload.ceremony_id)
+        .await?
+        .filter(|c| c.user_id.is_none())
+        .ok_or(AuthError::PasskeyCeremonyNotFound)?;
+
+    let authentication: DiscoverableAuthentication = serde_json::from_value(ceremony.state)?;
+
+    let (user_id, credential_id) = state.webauthn.identify_discoverable_authentication(&payload.credential)?;
+
+    let credential = state.repo
+        .webauthn_credential_get(credential_id)
+        .await?
+        .filter(|c| c.user_id == user_id)
+        .ok_or(AuthError::PasskeyNotFound)?;
+
+    let mut passkey: Passkey = serde_json::from_value(credential.passkey)?;
+    let result = state.webauthn.finish_discoverable_authentication(
+        &payload.credential,
+        authentication,
+        &[DiscoverableKey::from(&passkey)],
+    )?;
+
+    passkey.update_credential(&result);
+    state.repo
+        .webauthn_credential_mark_used(credential.id, serde_json::to_value(&passkey)?)
+        .await?;
+
+    // a passkey already verifies the user, so this skips the two-factor step
+    let tokens = AuthorizationClaim::start_session(&state.repo, user_id, &client).await?;
+
+    Ok(tokens.hx_redirect(&return_to::after_login(payload.next.as_deref())))
+}
+
+pub async fn delete(State(state): State<ServerState>, claim: AuthorizationClaim, Path(id): Path<Uuid>) -> Result<impl IntoResponse, AuthError> {
+    state.repo.webauthn_credential_delete(id, claim.user_id).await?;
+
+    render_passkeys(&state, claim.user_id).await
+}
@@ -2,8 +2,9 @@ use askama::Template;
 use axum::{body::Body, extract::State, response::IntoResponse, Form};
 use http::{header::SET_COOKIE, StatusCode};
 use serde::Deserialize;
+use tokio::time::Instant;
 
-use crate::{features::auth::{claims::{password_reset::PasswordResetClaim, Claims}, error::AuthError, handlers::views::password_reset::{CodeForm, ResetPasswordForm}}, repo::infra::user::UserRepo, utils::HxRedirect, web_service::server::ServerState};
+use crate::{features::auth::{privacy, claims::{password_reset::PasswordResetClaim, Claims}, error::AuthError, handlers::views::password_reset::{CodeForm, ResetPasswordForm}}, repo::infra::{password_reset::{PasswordResetOutcome, PasswordResetRepo}, personal_access_token::PersonalAccessTokenRepo, session::SessionRepo, user::UserRepo}, utils::HxRedirect, web_service::server::ServerState};
 
 
 #[derive(Template)]
@@ -13,6 +14,12 @@ pub struct OneTimeCodeEmailTemplate {
     expire_time: String,
 }
 
+#[derive(Template)]
+#[template(path = "auth/emails/no_account.html")]
+pub struct NoAccountEmailTemplate {
+    register_link: String,
+}
+
 #[derive(Deserialize)]
 pub struct EmailPayload {
     email: String,
@@ -20,23 +27,52 @@ pub struct EmailPayload {
 
 //this returns the code form
 pub async fn email_code(State(state): State<ServerState>, Form(payload): Form<EmailPayload>) -> Result<impl IntoResponse, AuthError> {
+    let started = Instant::now();
+    let privacy_mode = state.config.app.privacy_mode;
+
     let user = state.repo
         .user_get_by_email(payload.email.as_str())
-        .await?
-        .ok_or(AuthError::EmailNotFound)?;
+        .await?;
 
+    // without an account the claim is a decoy, no code is sent and it has no server side record
     let token = PasswordResetClaim::new(payload.email.clone());
-    let code = token.code.clone();
-    
-    let body = OneTimeCodeEmailTemplate {
-        code,
-        expire_time: PasswordResetClaim::EXPIRE_TIME_MINUTES.to_string(),
-    }
-    .render()?;
 
-    let message = state.mailer.create_message(body, user.email.clone(), user.name, "Email Code".to_string())?;
+    match user {
+        Some(user) => {
+            let code = PasswordResetClaim::generate_code();
+            state.repo
+                .password_reset_create(token.jti, user.id, &code, PasswordResetClaim::EXPIRE_TIME_MINUTES as i32)
+                .await?;
+
+            let body = OneTimeCodeEmailTemplate {
+                code,
+                expire_time: PasswordResetClaim::EXPIRE_TIME_MINUTES.to_string(),
+            }
+            .render()?;
+
+            let message = state.mailer.create_message(body, user.email.clone(), user.name, "Email Code".to_string())?;
+
+            if privacy_mode {
+                state.mailer.send_in_background(message);
+            } else {
+                state.mailer.send_message(message)?;
+            }
+        },
+        None if privacy_mode => {
+            let body = NoAccountEmailTemplate {
+                register_link: format!("{}/auth/register", state.config.app.origin.trim_end_matches('/')),
+            }
+            .render()?;
+
+            let message = state.mailer.create_message(body, payload.email.clone(), payload.email.clone(), "Email Code".to_string())?;
+            state.mailer.send_in_background(message);
+        },
+        None => return Err(AuthError::EmailNotFound),
+    }
 
-    state.mailer.send_message(message)?;
+    if privacy_mode {
+        privacy::uniform_delay(started).await;
+    }
 
     let body = CodeForm{
         email: payload.email,
@@ -58,23 +94,19 @@ pub struct CodeLoginPayload {
     code: String,
 }
 
-pub async fn code_login(claim: PasswordResetClaim ,Form(payload): Form<CodeLoginPayload>) -> Result<impl IntoResponse, AuthError> {
-    let mut claim = claim.clone();
+pub async fn code_login(State(state): State<ServerState>, claim: PasswordResetClaim ,Form(payload): Form<CodeLoginPayload>) -> Result<impl IntoResponse, AuthError> {
+    let code = PasswordResetClaim::normalize_code(&payload.code);
 
-    if !claim.authorize(payload.code.as_str()) {
-        return Err(AuthError::WrongPassword);
-    } 
+    match state.repo.password_reset_authorize(claim.jti, &code).await? {
+        PasswordResetOutcome::Authorized => {},
+        PasswordResetOutcome::Expired => return Err(AuthError::PasswordResetExpired),
+        // decoy claims from privacy mode have no record and just keep failing like a wrong code
+        PasswordResetOutcome::WrongCode | PasswordResetOutcome::NotFound => return Err(AuthError::WrongPassword),
+    }
 
     let body = ResetPasswordForm {}.render()?;
 
-    tracing::debug!("TOKEN: {:?}", claim);
-    let cookie = claim.cookie()?;
-
-
-    Ok((
-        [(SET_COOKIE, cookie)],
-        Body::from(body)
-    ).into_response())
+    Ok(Body::from(body).into_response())
 }
 
 #[derive(Deserialize)]
@@ -84,12 +116,7 @@ pub struct ChangePasswordPayload {
 }
 
 pub async fn change_password(State(state): State<ServerState>, claim: PasswordResetClaim, Form(payload): Form<ChangePasswordPayload>) -> Result<impl IntoResponse, AuthError> {
-    if !claim.authorized {
-        return Ok((
-            [(HxRedirect::HEADER_NAME, "/")],
-            StatusCode::UNAUTHORIZED,
-        ).into_response());
-    }
+    // whether the code was entered is checked when the reset is consumed, see `user_change_password`
     if payload.password != payload.confirm_password {
         return Err(AuthError::PasswordsDontMatch);
     }
@@ -106,8 +133,11 @@ pub async fn change_password(State(state): State<ServerState>, claim: PasswordRe
     let user = user.unwrap();
     
 
-    let _res = state.repo.user_change_password(user.id, payload.password, claim).await?;
-    
+    state.repo.user_change_password(user.id, payload.password, claim).await?;
+
+    // sign out every device and script, whoever knew the old password is kicked out too
+    state.repo.session_revoke_all_for_user(user.id).await?;
+    state.repo.personal_access_token_revoke_all_for_user(user.id).await?;
 
     Ok((
         [(HxRedirect::HEADER_NAME, "/auth/login")],
@@ -0,0 +1,66 @@
+use askama::Template;
+use axum::{extract::{Path, State}, response::{Html, IntoResponse}, Form};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    features::auth::{
+        claims::authorization::{AuthorizationClaim, SCOPE_READ, SCOPE_WRITE},
+        error::AuthError,
+        handlers::views::personal_access_token::{AccessTokenRow, AccessTokensFragment},
+    },
+    repo::infra::personal_access_token::PersonalAccessTokenRepo,
+    web_service::server::ServerState,
+};
+
+
+async fn render_tokens(state: &ServerState, user_id: Uuid, new_access_token: Option<String>) -> Result<Html<String>, AuthError> {
+    let access_tokens = state.repo
+        .personal_access_token_list_for_user(user_id)
+        .await?
+        .into_iter()
+        .map(AccessTokenRow::from)
+        .collect();
+
+    Ok(Html(AccessTokensFragment { access_tokens, new_access_token }.render()?))
+}
+
+#[derive(Deserialize)]
+pub struct CreatePayload {
+    name: String,
+    // checkboxes, only sent when ticked
+    read: Option<String>,
+    write: Option<String>,
+    // number of days, or "never"
+    expires_in: String,
+}
+
+pub async fn create(State(state): State<ServerState>, claim: AuthorizationClaim, Form(payload): Form<CreatePayload>) -> Result<impl IntoResponse, AuthError> {
+    let name = payload.name.trim();
+    let scopes: Vec<String> = [(payload.read.is_some(), SCOPE_READ), (payload.write.is_some(), SCOPE_WRITE)]
+        .into_iter()
+        .filter(|(ticked, _)| *ticked)
+        .map(|(_, scope)| scope.to_string())
+        .collect();
+
+    let expires_in_days = match payload.expires_in.as_str() {
+        "never" => None,
+        days => Some(days.parse::<i32>().ok().filter(|days| (1..=365).contains(days)).ok_or(AuthError::AccessTokenSettingsInvalid)?),
+    };
+
+    if name.is_empty() || name.chars().count() > 100 || scopes.is_empty() {
+        return Err(AuthError::AccessTokenSettingsInvalid);
+    }
+
+    let token = state.repo
+        .personal_access_token_create(claim.user_id, name, &scopes, expires_in_days)
+        .await?;
+
+    render_tokens(&state, claim.user_id, Some(token)).await
+}
+
+pub async fn revoke(State(state): State<ServerState>, claim: AuthorizationClaim, Path(id): Path<Uuid>) -> Result<impl IntoResponse, AuthError> {
+    state.repo.personal_access_token_revoke(id, claim.user_id).await?;
+
+    render_tokens(&state, claim.user_id, None).await
+}
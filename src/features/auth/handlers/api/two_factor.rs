@@ -0,0 +1,152 @@
+use askama::Template;
+use axum::{extract::State, response::{AppendHeaders, Html, IntoResponse}, Form};
+use http::header::SET_COOKIE;
+use serde::Deserialize;
+
+use crate::{
+    features::auth::{
+        claims::{authorization::AuthorizationClaim, two_factor::TwoFactorClaim, Claims},
+        current_user::CurrentUser,
+        error::AuthError,
+        handlers::views::two_factor::{TwoFactorSetup, TwoFactorStatus},
+        recovery, return_to, throttle, totp,
+    },
+    repo::infra::{recovery_code::RecoveryCodeRepo, totp::TotpRepo, two_factor_challenge::TwoFactorChallengeRepo, user::UserRepo},
+    utils::ClientInfo,
+    web_service::server::ServerState,
+};
+
+
+#[derive(Deserialize)]
+pub struct CodePayload {
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyPayload {
+    code: String,
+    next: Option<String>,
+}
+
+// second login step, the password was already checked when the claim was issued
+pub async fn verify(State(state): State<ServerState>, client: ClientInfo, claim: TwoFactorClaim, Form(payload): Form<VerifyPayload>) -> Result<impl IntoResponse, AuthError> {
+    let user = state.repo
+        .user_get_by_id(claim.user_id)
+        .await?
+        .ok_or(AuthError::TwoFactorChallengeExpired)?;
+
+    // wrong codes count against the account like wrong passwords, so signing in again for
+    // fresh attempts runs into the lockout
+    let throttle_key = throttle::key(&user.email);
+    let ip = client.ip.as_deref();
+    throttle::check(&state, &throttle_key, ip).await?;
+
+    if !recovery::verify_second_factor(&state, &claim, &payload.code).await? {
+        throttle::record_failure(&state, &throttle_key).await?;
+        return Err(AuthError::InvalidTwoFactorCode);
+    }
+
+    if !state.repo.two_factor_challenge_complete(claim.jti).await? {
+        return Err(AuthError::TwoFactorChallengeExpired);
+    }
+
+    throttle::record_success(&state, &throttle_key, ip).await?;
+
+    let tokens = AuthorizationClaim::start_session(&state.repo, claim.user_id, &client).await?;
+
+    Ok((
+        AppendHeaders([(SET_COOKIE, TwoFactorClaim::removal_cookie())]),
+        tokens.hx_redirect(&return_to::after_login(payload.next.as_deref())),
+    ))
+}
+
+pub async fn setup(State(state): State<ServerState>, CurrentUser { user, .. }: CurrentUser) -> Result<impl IntoResponse, AuthError> {
+    let existing = state.repo.totp_get(user.id).await?;
+    if existing.is_some_and(|t| t.is_enabled()) {
+        let remaining = state.repo.recovery_codes_remaining(user.id).await?;
+        return Ok(Html(TwoFactorStatus::enabled(remaining, vec![]).render()?));
+    }
+
+    let secret = totp::generate_secret();
+    let totp = totp::build(&secret, &user.email, &state.config.app.app_name)?;
+
+    state.repo.totp_set_pending(user.id, &secret).await?;
+
+    Ok(Html(TwoFactorSetup {
+        qr_base64: totp.to_qr_base64()?,
+        otpauth_url: totp.to_url()?,
+        secret,
+    }
+    .render()?))
+}
+
+pub async fn confirm(State(state): State<ServerState>, client: ClientInfo, claim: AuthorizationClaim, Form(payload): Form<CodePayload>) -> Result<impl IntoResponse, AuthError> {
+    if !verify_code_throttled(&state, &client, claim.user_id, &payload.code).await? {
+        return Err(AuthError::InvalidTwoFactorCode);
+    }
+
+    state.repo.totp_enable(claim.user_id).await?;
+
+    render_new_recovery_codes(&state, claim.user_id).await
+}
+
+pub async fn regenerate_recovery_codes(State(state): State<ServerState>, client: ClientInfo, claim: AuthorizationClaim, Form(payload): Form<CodePayload>) -> Result<impl IntoResponse, AuthError> {
+    let enabled = state.repo
+        .totp_get(claim.user_id)
+        .await?
+        .is_some_and(|t| t.is_enabled());
+
+    if !enabled || !verify_code_throttled(&state, &client, claim.user_id, &payload.code).await? {
+        return Err(AuthError::InvalidTwoFactorCode);
+    }
+
+    render_new_recovery_codes(&state, claim.user_id).await
+}
+
+// codes typed while signed in count against the same lockout as signing in,
+// so a stolen session can't guess its way to disabling two-factor
+async fn verify_code_throttled(state: &ServerState, client: &ClientInfo, user_id: uuid::Uuid, code: &str) -> Result<bool, AuthError> {
+    let user = state.repo
+        .user_get_by_id(user_id)
+        .await?
+        .ok_or(AuthError::EmailNotFound)?;
+
+    let throttle_key = throttle::key(&user.email);
+    let ip = client.ip.as_deref();
+    throttle::check(state, &throttle_key, ip).await?;
+
+    if !totp::verify_code(state, user_id, code).await? {
+        throttle::record_failure(state, &throttle_key).await?;
+        return Ok(false);
+    }
+
+    throttle::record_success(state, &throttle_key, ip).await?;
+
+    Ok(true)
+}
+
+// replaces the user's recovery codes and shows the new ones, this is the only time they are visible
+async fn render_new_recovery_codes(state: &ServerState, user_id: uuid::Uuid) -> Result<Html<String>, AuthError> {
+    let codes = recovery::generate_codes();
+    let normalized: Vec<String> = codes.iter().map(|code| recovery::normalize(code)).collect();
+
+    state.repo.recovery_codes_replace(user_id, &normalized).await?;
+
+    Ok(Html(TwoFactorStatus::enabled(codes.len() as i64, codes).render()?))
+}
+
+pub async fn disable(State(state): State<ServerState>, client: ClientInfo, claim: AuthorizationClaim, Form(payload): Form<CodePayload>) -> Result<impl IntoResponse, AuthError> {
+    let enabled = state.repo
+        .totp_get(claim.user_id)
+        .await?
+        .is_some_and(|t| t.is_enabled());
+
+    if enabled && !verify_code_throttled(&state, &client, claim.user_id, &payload.code).await? {
+        return Err(AuthError::InvalidTwoFactorCode);
+    }
+
+    state.repo.totp_delete(claim.user_id).await?;
+    state.repo.recovery_codes_delete(claim.user_id).await?;
+
+    Ok(Html(TwoFactorStatus::disabled().render()?))
+}
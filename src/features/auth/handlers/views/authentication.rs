@@ -1,16 +1,75 @@
 use askama::Template;
+use axum::{extract::{Query, State}, response::Html};
 
+use crate::{features::auth::return_to::{self, NextQuery}, web_service::server::ServerState, ServerError};
 
 
 
+/// A "Sign in with ..." link for a configured OpenID Connect provider
+pub struct OidcProviderLink {
+    pub id: String,
+    pub name: String,
+}
 
+impl OidcProviderLink {
+    pub fn all(state: &ServerState) -> Vec<Self> {
+        state.config.oidc
+            .iter()
+            .map(|(id, provider)| Self { id: id.clone(), name: provider.display_name.clone() })
+            .collect()
+    }
+}
 
 #[derive(Template)]
 #[template(path = "auth/login.html")]
-pub struct LoginTemplate;
+pub struct LoginTemplate {
+    providers: Vec<OidcProviderLink>,
+    // where to go once signed in, see `return_to`
+    next: Option<String>,
+}
+
+impl LoginTemplate {
+    pub async fn handler(State(state): State<ServerState>, Query(query): Query<NextQuery>) -> Result<Html<String>, ServerError> {
+        Ok(Html(Self {
+            providers: OidcProviderLink::all(&state),
+            next: return_to::sanitize(query.next.as_deref()),
+        }.render()?))
+    }
+}
 
 #[derive(Template)]
 #[template(path = "auth/register.html")]
-pub struct RegisterTemplate;
+pub struct RegisterTemplate {
+    providers: Vec<OidcProviderLink>,
+}
+
+impl RegisterTemplate {
+    pub async fn handler(State(state): State<ServerState>) -> Result<Html<String>, ServerError> {
+        Ok(Html(Self { providers: OidcProviderLink::all(&state) }.render()?))
+    }
+}
+
+#[derive(Template)]
+#[template(path = "auth/fragments/error/too_many_attempts.html")]
+pub struct TooManyAttempts {
+    wait: String,
+}
+
+impl TooManyAttempts {
+    pub fn new(retry_after_seconds: u64) -> Self {
+        let wait = match retry_after_seconds {
+            0..=59 => format!("{} seconds", retry_after_seconds),
+            60..=119 => "a minute".to_string(),
+            seconds => format!("{} minutes", seconds.div_ceil(60)),
+        };
 
+        Self { wait }
+    }
+}
 
+/// The privacy mode answer to a registration, whether or not the email already had an account
+#[derive(Template)]
+#[template(path = "auth/fragments/registration_pending.html")]
+pub struct RegistrationPending {
+    pub email: String,
+}
@@ -0,0 +1,9 @@
+use askama::Template;
+
+
+
+#[derive(Template)]
+#[template(path = "auth/fragments/email_change_sent.html")]
+pub struct EmailChangeSent {
+    pub new_email: String,
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+
+
+#[derive(Template)]
+#[template(path = "auth/fragments/verification_sent.html")]
+pub struct VerificationSent {
+    pub email: String,
+    pub already_verified: bool,
+}
@@ -0,0 +1,30 @@
+use askama::Template;
+use axum::{extract::Query, response::Html};
+use serde::Deserialize;
+
+use crate::ServerError;
+
+
+
+#[derive(Template)]
+#[template(path = "auth/magic_link.html")]
+pub struct MagicLinkTemplate {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkQuery {
+    token: String,
+}
+
+impl MagicLinkTemplate {
+    pub async fn handler(Query(query): Query<MagicLinkQuery>) -> Result<Html<String>, ServerError> {
+        Ok(Html(Self { token: query.token }.render()?))
+    }
+}
+
+#[derive(Template)]
+#[template(path = "auth/fragments/magic_link_sent.html")]
+pub struct MagicLinkSent {
+    pub email: String,
+}
@@ -1,2 +1,8 @@
 pub mod password_reset;
-pub mod authentication;
\ No newline at end of file
+pub mod authentication;
+pub mod email_change;
+pub mod email_verification;
+pub mod magic_link;
+pub mod passkey;
+pub mod personal_access_token;
+pub mod two_factor;
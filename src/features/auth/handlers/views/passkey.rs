@@ -0,0 +1,32 @@
+use askama::Template;
+
+use crate::repo::infra::webauthn::WebauthnCredential;
+
+
+
+pub struct PasskeyRow {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub last_used_at: String,
+}
+
+impl From<WebauthnCredential> for PasskeyRow {
+    fn from(credential: WebauthnCredential) -> Self {
+        Self {
+            id: credential.id.to_string(),
+            name: credential.name,
+            created_at: credential.created_at.format("%Y-%m-%d").to_string(),
+            last_used_at: credential
+                .last_used_at
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "Never".to_string()),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "auth/fragments/passkeys.html")]
+pub struct PasskeysFragment {
+    pub passkeys: Vec<PasskeyRow>,
+}
@@ -0,0 +1,43 @@
+use askama::Template;
+
+use crate::repo::infra::personal_access_token::PersonalAccessToken;
+
+
+
+pub struct AccessTokenRow {
+    pub id: String,
+    pub name: String,
+    pub scopes: String,
+    pub created_at: String,
+    pub expires_at: String,
+    pub last_used_at: String,
+    pub is_expired: bool,
+}
+
+impl From<PersonalAccessToken> for AccessTokenRow {
+    fn from(token: PersonalAccessToken) -> Self {
+        Self {
+            id: token.id.to_string(),
+            is_expired: token.is_expired(),
+            name: token.name,
+            scopes: token.scopes.join(", "),
+            created_at: token.created_at.format("%Y-%m-%d").to_string(),
+            expires_at: token
+                .expires_at
+                .map(|t| t.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "Never".to_string()),
+            last_used_at: token
+                .last_used_at
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "Never".to_string()),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "auth/fragments/personal_access_tokens.html")]
+pub struct AccessTokensFragment {
+    pub access_tokens: Vec<AccessTokenRow>,
+    // shown once right after it was created, only its hash is kept
+    pub new_access_token: Option<String>,
+}
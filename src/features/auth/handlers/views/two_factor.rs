@@ -0,0 +1,66 @@
+use askama::Template;
+use axum::{extract::Query, response::Html};
+
+use crate::{features::auth::{recovery::LOW_RECOVERY_CODES, return_to::{self, NextQuery}}, ServerError};
+
+
+
+#[derive(Template)]
+#[template(path = "auth/fragments/forms/two_factor/verify.html")]
+pub struct TwoFactorForm {
+    // carried over from the password step, see `return_to`
+    pub next: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "auth/fragments/forms/two_factor/setup.html")]
+pub struct TwoFactorSetup {
+    pub qr_base64: String,
+    pub otpauth_url: String,
+    pub secret: String,
+}
+
+#[derive(Template)]
+#[template(path = "auth/fragments/two_factor/status.html")]
+pub struct TwoFactorStatus {
+    pub two_factor_enabled: bool,
+    pub recovery_codes_remaining: i64,
+    pub recovery_codes_low: bool,
+    // only set right after the codes are generated, they cannot be shown again
+    pub new_recovery_codes: Vec<String>,
+}
+
+impl TwoFactorStatus {
+    pub fn disabled() -> Self {
+        Self {
+            two_factor_enabled: false,
+            recovery_codes_remaining: 0,
+            recovery_codes_low: false,
+            new_recovery_codes: vec![],
+        }
+    }
+
+    pub fn enabled(recovery_codes_remaining: i64, new_recovery_codes: Vec<String>) -> Self {
+        Self {
+            two_factor_enabled: true,
+            recovery_codes_remaining,
+            recovery_codes_low: recovery_codes_remaining <= LOW_RECOVERY_CODES,
+            new_recovery_codes,
+        }
+    }
+}
+
+/// Standalone second step for sign ins that arrive through a redirect, e.g. an identity provider
+#[derive(Template)]
+#[template(path = "auth/two_factor.html")]
+pub struct TwoFactorPage {
+    // shares the form with `TwoFactorForm`, these sign ins don't come from the login page
+    // so `next` arrives in the query string
+    pub next: Option<String>,
+}
+
+impl TwoFactorPage {
+    pub async fn handler(Query(query): Query<NextQuery>) -> Result<Html<String>, ServerError> {
+        Ok(Html(Self { next: return_to::sanitize(query.next.as_deref()) }.render()?))
+    }
+}
@@ -0,0 +1,101 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::Cookie;
+use http::{header, HeaderValue};
+
+use crate::{
+    repo::infra::refresh_token::{RefreshOutcome, RefreshTokenRepo},
+    utils::extract_cookie_value,
+    web_service::server::ServerState,
+};
+
+use super::claims::{authorization::{AuthorizationClaim, RequireSession}, refresh::RefreshToken, Claims};
+
+
+/// Exchanges the refresh token for a new access token when the access token is missing or expired.
+/// The new access token is written into the request cookies so extractors further down see it,
+/// and both rotated cookies are set on the response.
+pub async fn refresh_session(State(state): State<ServerState>, mut request: Request, next: Next) -> Response {
+    let cookies_str = request
+        .headers()
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let access_token = extract_cookie_value(&cookies_str, &AuthorizationClaim::cookie_name());
+    if access_token.is_some_and(|token| AuthorizationClaim::from_token(token).is_ok()) {
+        return next.run(request).await;
+    }
+
+    let Some(refresh_token) = extract_cookie_value(&cookies_str, &RefreshToken::cookie_name()) else {
+        return next.run(request).await;
+    };
+
+    let outcome = match state.repo.refresh_token_rotate(&refresh_token).await {
+        Ok(outcome) => outcome,
+        Err(err) => return err.into_response(),
+    };
+
+    let (user_id, session_id, refresh_token) = match outcome {
+        RefreshOutcome::Rotated { user_id, session_id, token } => {
+            (user_id, session_id, Some(RefreshToken(token)))
+        },
+        RefreshOutcome::Concurrent { user_id, session_id } => {
+            (user_id, session_id, None)
+        },
+        RefreshOutcome::Reused | RefreshOutcome::Invalid => {
+            let mut response = next.run(request).await;
+            if let Ok(cookie) = HeaderValue::from_str(&RefreshToken::removal_cookie()) {
+                response.headers_mut().append(header::SET_COOKIE, cookie);
+            }
+            return response;
+        }
+    };
+
+    // a refresh also picks up role and membership changes made since the last access token
+    let claim = match AuthorizationClaim::issue(&state.repo, user_id, session_id).await {
+        Ok(claim) => claim,
+        Err(err) => return err.into_response(),
+    };
+
+    let (token, access_cookie) = match claim.token().and_then(|token| Ok((token, claim.cookie()?))) {
+        Ok(res) => res,
+        Err(err) => return err.into_response(),
+    };
+
+    let cookies_str = replace_cookie_value(&cookies_str, &AuthorizationClaim::cookie_name(), &token);
+    if let Ok(cookies) = HeaderValue::from_str(&cookies_str) {
+        request.headers_mut().insert(header::COOKIE, cookies);
+    }
+
+    let mut response = next.run(request).await;
+
+    let set_cookies = std::iter::once(access_cookie).chain(refresh_token.map(|t| t.cookie()));
+    for cookie in set_cookies {
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+
+    response
+}
+
+/// Route layer version of `RequireSession`, for routes a personal access token must not reach
+pub async fn require_session(_: RequireSession, request: Request, next: Next) -> Response {
+    next.run(request).await
+}
+
+// rebuilds a Cookie header with `name` set to `value`, keeping every other cookie
+fn replace_cookie_value(cookies_str: &str, name: &str, value: &str) -> String {
+    Cookie::split_parse(cookies_str)
+        .filter_map(|c| c.ok())
+        .filter(|c| c.name() != name)
+        .map(|c| c.stripped().to_string())
+        .chain(std::iter::once(Cookie::new(name, value).to_string()))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
@@ -1,3 +1,14 @@
 pub mod error;
 pub mod claims;
-pub mod handlers;
\ No newline at end of file
+pub mod csrf;
+pub mod current_user;
+pub mod handlers;
+pub mod middleware;
+pub mod permissions;
+pub mod oidc;
+pub mod privacy;
+pub mod recovery;
+pub mod return_to;
+pub mod roles;
+pub mod throttle;
+pub mod totp;
\ No newline at end of file
@@ -0,0 +1,49 @@
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+
+
+
+#[derive(Debug, derive_more::From)]
+pub enum OidcError {
+    UnknownProvider,
+    // the callback does not belong to the request this browser started
+    StateMismatch,
+    NonceMismatch,
+    // the discovery document names another issuer than the one configured
+    IssuerMismatch,
+    MissingIdToken,
+    SigningKeyNotFound,
+    EmailNotVerified,
+    // the provider redirected back with an `error` parameter, e.g. the user cancelled
+    ProviderError(String),
+    #[from]
+    Http(reqwest::Error),
+    #[from]
+    Url(url::ParseError),
+    #[from]
+    IdToken(jsonwebtoken::errors::Error),
+}
+
+impl IntoResponse for OidcError {
+    fn into_response(self) -> Response {
+        match self {
+            OidcError::UnknownProvider => {
+                (StatusCode::NOT_FOUND, "Unknown sign in provider").into_response()
+            },
+            OidcError::StateMismatch | OidcError::NonceMismatch => {
+                (StatusCode::BAD_REQUEST, "This sign in attempt expired, please try again").into_response()
+            },
+            OidcError::EmailNotVerified => {
+                (StatusCode::FORBIDDEN, "Your email address is not verified with this provider").into_response()
+            },
+            OidcError::ProviderError(err) => {
+                tracing::debug!("Identity provider returned an error: {}", err);
+                (StatusCode::UNAUTHORIZED, "Sign in was cancelled or denied by the provider").into_response()
+            },
+            err => {
+                tracing::error!("An oidc error occured: {:?}", err);
+                (StatusCode::BAD_GATEWAY, "Could not sign in with this provider, please try again later").into_response()
+            }
+        }
+    }
+}
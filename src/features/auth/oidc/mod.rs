@@ -0,0 +1,221 @@
+pub mod error;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use error::OidcError;
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::config::OidcProviderConfig;
+
+use super::claims::oidc::OidcStateClaim;
+
+
+/// The parts of `/.well-known/openid-configuration` the authorization code flow needs
+#[derive(Deserialize, Debug)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+/// The verified identity from the provider's id token
+#[derive(Deserialize, Debug)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub nonce: Option<String>,
+}
+
+struct Cached<T> {
+    value: Arc<T>,
+    fetched: Instant,
+}
+
+/// Talks to identity providers, remembering their discovery documents and signing keys
+/// for `ttl` so a sign in doesn't cost two extra round trips to the provider
+pub struct OidcClient {
+    http: reqwest::Client,
+    ttl: Duration,
+    // keyed by the configured issuer
+    metadata: Mutex<HashMap<String, Cached<ProviderMetadata>>>,
+    // keyed by the jwks uri
+    jwks: Mutex<HashMap<String, Cached<JwkSet>>>,
+}
+
+impl OidcClient {
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+    pub fn new(http: reqwest::Client) -> Self {
+        Self::with_ttl(http, Self::DEFAULT_TTL)
+    }
+
+    pub fn with_ttl(http: reqwest::Client, ttl: Duration) -> Self {
+        Self {
+            http,
+            ttl,
+            metadata: Mutex::default(),
+            jwks: Mutex::default(),
+        }
+    }
+
+    fn cached<T>(&self, cache: &Mutex<HashMap<String, Cached<T>>>, key: &str) -> Option<Arc<T>> {
+        cache.lock().unwrap()
+            .get(key)
+            .filter(|cached| cached.fetched.elapsed() < self.ttl)
+            .map(|cached| cached.value.clone())
+    }
+
+    fn store<T>(cache: &Mutex<HashMap<String, Cached<T>>>, key: &str, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        cache.lock().unwrap().insert(key.to_string(), Cached { value: value.clone(), fetched: Instant::now() });
+        value
+    }
+
+    pub async fn discover(&self, provider: &OidcProviderConfig) -> Result<Arc<ProviderMetadata>, OidcError> {
+        if let Some(metadata) = self.cached(&self.metadata, &provider.issuer) {
+            return Ok(metadata);
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", provider.issuer.trim_end_matches('/'));
+
+        let metadata = self.http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ProviderMetadata>()
+            .await?;
+
+        // otherwise whoever serves the document could vouch for tokens of another issuer
+        if metadata.issuer.trim_end_matches('/') != provider.issuer.trim_end_matches('/') {
+            return Err(OidcError::IssuerMismatch);
+        }
+
+        Ok(Self::store(&self.metadata, &provider.issuer, metadata))
+    }
+
+    async fn signing_keys(&self, jwks_uri: &str, refresh: bool) -> Result<Arc<JwkSet>, OidcError> {
+        if !refresh && let Some(jwks) = self.cached(&self.jwks, jwks_uri) {
+            return Ok(jwks);
+        }
+
+        let jwks = self.http
+            .get(jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await?;
+
+        Ok(Self::store(&self.jwks, jwks_uri, jwks))
+    }
+
+    /// Exchanges the authorization code and returns the validated id token claims
+    pub async fn exchange_code(
+        &self,
+        metadata: &ProviderMetadata,
+        provider: &OidcProviderConfig,
+        redirect_uri: &str,
+        code: &str,
+        claim: &OidcStateClaim,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let tokens = self.http
+            .post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+                ("client_id", provider.client_id.as_str()),
+                ("client_secret", provider.client_secret.as_str()),
+                ("code_verifier", claim.code_verifier.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<TokenResponse>()
+            .await?;
+
+        let id_token = tokens.id_token.ok_or(OidcError::MissingIdToken)?;
+        let claims = self.validate_id_token(metadata, provider, &id_token).await?;
+
+        if claims.nonce.as_deref() != Some(claim.nonce.as_str()) {
+            return Err(OidcError::NonceMismatch);
+        }
+
+        Ok(claims)
+    }
+
+    async fn validate_id_token(&self, metadata: &ProviderMetadata, provider: &OidcProviderConfig, id_token: &str) -> Result<IdTokenClaims, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+
+        // id tokens are verified against the provider's published keys, never a shared secret
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(OidcError::SigningKeyNotFound);
+        }
+
+        let mut jwks = self.signing_keys(&metadata.jwks_uri, false).await?;
+
+        // an unknown kid usually means the provider rotated its keys since they were cached
+        if header.kid.as_ref().is_some_and(|kid| jwks.find(kid).is_none()) {
+            jwks = self.signing_keys(&metadata.jwks_uri, true).await?;
+        }
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or(OidcError::SigningKeyNotFound)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[provider.client_id.as_str()]);
+        validation.set_issuer(&[metadata.issuer.as_str()]);
+
+        let token_data = jsonwebtoken::decode::<IdTokenClaims>(id_token, &DecodingKey::from_jwk(jwk)?, &validation)?;
+
+        Ok(token_data.claims)
+    }
+}
+
+pub fn redirect_uri(origin: &str, provider_id: &str) -> String {
+    format!("{}/api/auth/oidc/{}/callback", origin.trim_end_matches('/'), provider_id)
+}
+
+/// The S256 PKCE challenge for a verifier
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+pub fn authorization_url(metadata: &ProviderMetadata, provider: &OidcProviderConfig, redirect_uri: &str, claim: &OidcStateClaim) -> Result<Url, OidcError> {
+    let url = Url::parse_with_params(
+        &metadata.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", provider.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("scope", provider.scopes.join(" ").as_str()),
+            ("state", claim.state.as_str()),
+            ("nonce", claim.nonce.as_str()),
+            ("code_challenge", code_challenge(&claim.code_verifier).as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )?;
+
+    Ok(url)
+}
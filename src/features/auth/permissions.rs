@@ -0,0 +1,129 @@
+//! Fine grained permissions like `users:read`. They are granted to roles (the groups users are in)
+//! or straight to a user, each feature declares its own with `permissions!`, see `features::admin::permissions`.
+//!
+//! Routes declare what they need with `RequirePermission<P>` or
+//! `.route_layer(from_fn_with_state(state, require_permission::<P>))`, checks that depend on the
+//! resource itself go through a `Policy`.
+
+use std::{collections::HashSet, marker::PhantomData, sync::Arc};
+
+use axum::{
+    extract::{FromRef, FromRequestParts, Request},
+    middleware::Next,
+    response::Response,
+};
+use http::request::Parts;
+use uuid::Uuid;
+
+use crate::{repo::infra::permission::PermissionRepo, utils::is_htmx_request, web_service::server::ServerState};
+
+use super::{claims::authorization::AuthorizationClaim, roles::AccessRejection};
+
+
+pub trait Permission: Send + Sync + 'static {
+    /// The row in the `permissions` table
+    const NAME: &'static str;
+}
+
+/// Declares unit structs implementing `Permission`, the names also need a row in `permissions`
+macro_rules! permissions {
+    ($($(#[$meta:meta])* $ty:ident = $name:literal;)*) => {
+        $(
+            $(#[$meta])*
+            pub struct $ty;
+
+            impl $crate::features::auth::permissions::Permission for $ty {
+                const NAME: &'static str = $name;
+            }
+        )*
+    };
+}
+pub(crate) use permissions;
+
+/// Decides whether a subject may act on a specific resource, for checks a permission alone can't express
+pub trait Policy<R: ?Sized> {
+    fn allows(subject: &Subject, resource: &R) -> bool;
+}
+
+/// The signed in user and everything they are allowed to do.
+/// Loaded once per request, later extractions (e.g. a layer and then the handler) reuse it.
+#[derive(Debug, Clone)]
+pub struct Subject {
+    pub claim: AuthorizationClaim,
+    permissions: Arc<HashSet<String>>,
+}
+
+impl Subject {
+    pub fn user_id(&self) -> Uuid {
+        self.claim.user_id
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    pub fn has<P: Permission>(&self) -> bool {
+        self.has_permission(P::NAME)
+    }
+
+    pub fn can<P: Policy<R>, R: ?Sized>(&self, resource: &R) -> bool {
+        P::allows(self, resource)
+    }
+}
+
+impl<S> FromRequestParts<S> for Subject
+where
+    ServerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AccessRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(subject) = parts.extensions.get::<Subject>() {
+            return Ok(subject.clone());
+        }
+
+        let claim = AuthorizationClaim::from_request_parts(parts, state).await?;
+
+        let state = ServerState::from_ref(state);
+        let permissions = state.repo.user_permissions(claim.user_id).await?;
+
+        let subject = Subject {
+            claim,
+            permissions: Arc::new(permissions.into_iter().collect()),
+        };
+        parts.extensions.insert(subject.clone());
+
+        Ok(subject)
+    }
+}
+
+/// The signed in user, if they have the permission `P`
+pub struct RequirePermission<P: Permission> {
+    pub subject: Subject,
+    permission: PhantomData<fn() -> P>,
+}
+
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    ServerState: FromRef<S>,
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = AccessRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let subject = Subject::from_request_parts(parts, state).await?;
+
+        if !subject.has::<P>() {
+            return Err(AccessRejection::Forbidden { htmx: is_htmx_request(&parts.headers) });
+        }
+
+        Ok(Self { subject, permission: PhantomData })
+    }
+}
+
+/// Route layer version of `RequirePermission`, the loaded `Subject` stays cached for the handler
+pub async fn require_permission<P: Permission>(_: RequirePermission<P>, request: Request, next: Next) -> Response {
+    next.run(request).await
+}
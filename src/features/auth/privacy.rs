@@ -0,0 +1,22 @@
+//! Privacy mode keeps login, registration and password reset from revealing which emails
+//! have accounts. Both outcomes get the same response, the difference is only told by email,
+//! and responses are held back to a common minimum time so they cant be told apart by timing.
+
+use std::time::Duration;
+
+use argon2::{password_hash::{rand_core::OsRng, PasswordHasher, SaltString}, Argon2};
+use tokio::time::Instant;
+
+// comfortably above a password hash plus a few queries
+const RESPONSE_FLOOR: Duration = Duration::from_millis(1500);
+
+/// Waits until the response floor has passed since `started`
+pub async fn uniform_delay(started: Instant) {
+    tokio::time::sleep_until(started + RESPONSE_FLOOR).await;
+}
+
+/// Does the work of hashing a password for the branch that otherwise would not, so it takes as long
+pub fn dummy_password_hash(password: &str) {
+    let salt = SaltString::generate(&mut OsRng);
+    let _ = Argon2::default().hash_password(password.as_bytes(), &salt);
+}
@@ -0,0 +1,98 @@
+use rand::Rng;
+
+use crate::{repo::infra::{recovery_code::RecoveryCodeRepo, two_factor_challenge::TwoFactorChallengeRepo}, web_service::server::ServerState};
+
+use super::{claims::two_factor::TwoFactorClaim, error::AuthError, totp};
+
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+// the dashboard warns the user to regenerate once this few are left
+pub const LOW_RECOVERY_CODES: i64 = 3;
+
+// no 0/O, 1/I so codes can be read off a printout
+const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const HALF_LENGTH: usize = 5;
+
+/// Generates a fresh set of codes formatted like `ABCDE-FGHJK`
+pub fn generate_codes() -> Vec<String> {
+    let mut rng = rand::rng();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut half = || -> String {
+                (0..HALF_LENGTH)
+                    .map(|_| ALPHABET[rng.random_range(0..ALPHABET.len())] as char)
+                    .collect()
+            };
+            format!("{}-{}", half(), half())
+        })
+        .collect()
+}
+
+/// Strips the formatting so `abcde fghjk` and `ABCDE-FGHJK` are the same code
+pub fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// whether a normalized code could be one of ours, anything else isn't worth the argon2 checks
+fn is_well_formed(code: &str) -> bool {
+    code.len() == HALF_LENGTH * 2 && code.bytes().all(|c| ALPHABET.contains(&c))
+}
+
+/// Accepts either a TOTP code or one of the user's recovery codes in the second login step.
+/// Every call first uses up one of the challenge's attempts, checking a recovery code costs an
+/// argon2 verification per unused code so they must not be guessable without limit
+pub async fn verify_second_factor(state: &ServerState, claim: &TwoFactorClaim, code: &str) -> Result<bool, AuthError> {
+    if !state.repo.two_factor_challenge_attempt(claim.jti, claim.user_id).await? {
+        return Err(AuthError::TwoFactorChallengeExpired);
+    }
+
+    let code = code.trim();
+
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        return totp::verify_code(state, claim.user_id, code).await;
+    }
+
+    let code = normalize(code);
+    if !is_well_formed(&code) {
+        return Ok(false);
+    }
+
+    Ok(state.repo.recovery_code_use(claim.user_id, &code).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_ignores_case_and_formatting() {
+        assert_eq!(normalize("ABCDE-FGHJK"), "ABCDEFGHJK");
+        assert_eq!(normalize("abcde fghjk"), "ABCDEFGHJK");
+        assert_eq!(normalize("  aBcDe--FgHjK\n"), "ABCDEFGHJK");
+    }
+
+    #[test]
+    fn generated_codes_are_well_formed_once_normalized() {
+        let codes = generate_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in codes {
+            assert_eq!(code.len(), HALF_LENGTH * 2 + 1);
+            assert!(is_well_formed(&normalize(&code)), "{code}");
+        }
+    }
+
+    #[test]
+    fn rejects_codes_that_cant_be_ours() {
+        // 0, O, 1 and I are left out of the alphabet
+        assert!(!is_well_formed(&normalize("ABCDE-FGHJ0")));
+        assert!(!is_well_formed(&normalize("ABCDE-FGHJI")));
+        assert!(!is_well_formed(&normalize("ABCDE")));
+        assert!(!is_well_formed(&normalize("ABCDE-FGHJK-LMNPQ")));
+        assert!(!is_well_formed(""));
+    }
+}
@@ -0,0 +1,157 @@
+//! The `next` parameter, where someone is sent back to once they signed in.
+//! It comes from the query string or a form field, so only local paths are ever followed.
+
+use axum::extract::OriginalUri;
+use http::request::Parts;
+use serde::Deserialize;
+
+use crate::utils::is_htmx_request;
+
+use super::claims::{authorization::AuthorizationClaim, Claims};
+
+
+pub const LOGIN_URI: &str = "/auth/login";
+pub const TWO_FACTOR_URI: &str = "/auth/two-factor";
+
+#[derive(Deserialize)]
+pub struct NextQuery {
+    pub next: Option<String>,
+}
+
+/// `next` if it points into this site, anything else (`//evil.com`, `https://...`) is dropped
+pub fn sanitize(next: Option<&str>) -> Option<String> {
+    let next = next?;
+
+    let is_local = next.starts_with('/')
+        && !next.starts_with("//")
+        && !next.contains('\\')
+        && !next.chars().any(char::is_control);
+
+    // sending someone back to the login page after signing in would be a loop
+    let is_login = next == LOGIN_URI || next.starts_with(&format!("{}?", LOGIN_URI));
+
+    (is_local && !is_login).then(|| next.to_string())
+}
+
+/// Where to go after a successful sign in
+pub fn after_login(next: Option<&str>) -> String {
+    sanitize(next).unwrap_or_else(|| AuthorizationClaim::SUCCESS_REDIRECT_URI.to_string())
+}
+
+pub fn login_url(next: Option<&str>) -> String {
+    with_next(LOGIN_URI, next)
+}
+
+/// The standalone second step, for sign ins that arrive through a redirect
+pub fn two_factor_url(next: Option<&str>) -> String {
+    with_next(TWO_FACTOR_URI, next)
+}
+
+fn with_next(uri: &str, next: Option<&str>) -> String {
+    match sanitize(next) {
+        Some(next) => format!("{}?next={}", uri, url::form_urlencoded::byte_serialize(next.as_bytes()).collect::<String>()),
+        None => uri.to_string(),
+    }
+}
+
+/// The page to come back to after signing in. For htmx requests that is the page the request came
+/// from, not the api endpoint it called, other requests only come back if they were a plain GET.
+pub fn from_request(parts: &Parts) -> Option<String> {
+    if is_htmx_request(&parts.headers) {
+        let current_url = parts.headers.get("HX-Current-URL")?.to_str().ok()?;
+        let current_url = url::Url::parse(current_url).ok()?;
+
+        let next = match current_url.query() {
+            Some(query) => format!("{}?{}", current_url.path(), query),
+            None => current_url.path().to_string(),
+        };
+        return sanitize(Some(&next));
+    }
+
+    if parts.method != http::Method::GET {
+        return None;
+    }
+
+    // nested routers only see the part of the path below their prefix
+    let uri = parts.extensions.get::<OriginalUri>().map(|uri| &uri.0).unwrap_or(&parts.uri);
+    sanitize(uri.path_and_query().map(|p| p.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(method: http::Method, uri: &str, headers: &[(&str, &str)]) -> Parts {
+        let mut request = http::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn keeps_local_paths() {
+        assert_eq!(sanitize(Some("/dashboard")), Some("/dashboard".to_string()));
+        assert_eq!(sanitize(Some("/orgs/invitations/accept?token=abc")), Some("/orgs/invitations/accept?token=abc".to_string()));
+        assert_eq!(sanitize(None), None);
+    }
+
+    #[test]
+    fn drops_other_sites() {
+        for next in ["https://evil.com", "//evil.com", "///evil.com", "evil.com", "javascript:alert(1)", ""] {
+            assert_eq!(sanitize(Some(next)), None, "{next}");
+        }
+    }
+
+    #[test]
+    fn drops_paths_browsers_read_as_another_host() {
+        // browsers treat a backslash like a slash, and skip tabs and newlines in urls
+        for next in ["/\\evil.com", "/\\/evil.com", "/a\\b", "/\t/evil.com", "/\n/evil.com", "/\r\n/evil.com"] {
+            assert_eq!(sanitize(Some(next)), None, "{next:?}");
+        }
+    }
+
+    #[test]
+    fn drops_the_login_page() {
+        assert_eq!(sanitize(Some(LOGIN_URI)), None);
+        assert_eq!(sanitize(Some("/auth/login?next=/dashboard")), None);
+        assert_eq!(sanitize(Some("/auth/login-help")), Some("/auth/login-help".to_string()));
+    }
+
+    #[test]
+    fn falls_back_to_the_dashboard() {
+        assert_eq!(after_login(Some("/settings")), "/settings");
+        assert_eq!(after_login(Some("//evil.com")), AuthorizationClaim::SUCCESS_REDIRECT_URI);
+        assert_eq!(after_login(None), AuthorizationClaim::SUCCESS_REDIRECT_URI);
+    }
+
+    #[test]
+    fn login_url_encodes_next() {
+        assert_eq!(login_url(Some("/orgs?a=1&b=2")), "/auth/login?next=%2Forgs%3Fa%3D1%26b%3D2");
+        assert_eq!(login_url(Some("https://evil.com")), LOGIN_URI);
+        assert_eq!(login_url(None), LOGIN_URI);
+        assert_eq!(two_factor_url(Some("/settings")), "/auth/two-factor?next=%2Fsettings");
+    }
+
+    #[test]
+    fn htmx_requests_come_back_to_the_page_they_came_from() {
+        let request = parts(http::Method::POST, "/api/sessions/revoke-others", &[
+            ("HX-Request", "true"),
+            ("HX-Current-URL", "http://localhost:8000/dashboard?tab=sessions"),
+        ]);
+        assert_eq!(from_request(&request), Some("/dashboard?tab=sessions".to_string()));
+
+        // only the path of the current url is kept, whatever host it names
+        let request = parts(http::Method::POST, "/api/orgs", &[
+            ("HX-Request", "true"),
+            ("HX-Current-URL", "https://evil.com/dashboard"),
+        ]);
+        assert_eq!(from_request(&request), Some("/dashboard".to_string()));
+    }
+
+    #[test]
+    fn only_plain_gets_come_back() {
+        assert_eq!(from_request(&parts(http::Method::GET, "/dashboard?tab=tokens", &[])), Some("/dashboard?tab=tokens".to_string()));
+        assert_eq!(from_request(&parts(http::Method::POST, "/api/orgs", &[])), None);
+    }
+}
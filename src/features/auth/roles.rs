@@ -0,0 +1,66 @@
+//! Role based access control. Roles live in `user_roles` and are copied into the
+//! `AuthorizationClaim` when it is issued, `users.role_version` tells whether that copy is stale.
+//!
+//! Access checks go through the permissions granted to these roles, see `permissions::RequirePermission`.
+
+use askama::Template;
+use axum::response::{Html, IntoResponse, Response};
+use http::StatusCode;
+
+use crate::repo::error::RepoError;
+
+use super::claims::error::ClaimsError;
+
+
+pub trait Role: Send + Sync + 'static {
+    /// The row in the `roles` table
+    const NAME: &'static str;
+}
+
+pub struct Admin;
+
+impl Role for Admin {
+    const NAME: &'static str = "admin";
+}
+
+#[derive(Template)]
+#[template(path = "forbidden.html")]
+struct ForbiddenPage {}
+
+#[derive(Template)]
+#[template(path = "fragments/forbidden.html")]
+struct ForbiddenFragment {}
+
+#[derive(Debug, derive_more::From)]
+pub enum AccessRejection {
+    #[from]
+    Claims(ClaimsError),
+    /// Signed in but without the role or permission, htmx requests get a fragment instead of the full page
+    Forbidden { htmx: bool },
+    #[from]
+    Repo(RepoError),
+}
+
+impl IntoResponse for AccessRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Claims(err) => err.into_response(),
+            Self::Forbidden { htmx } => {
+                let body = if htmx {
+                    ForbiddenFragment {}.render()
+                } else {
+                    ForbiddenPage {}.render()
+                };
+
+                match body {
+                    Ok(body) => (StatusCode::FORBIDDEN, Html(body)).into_response(),
+                    Err(err) => {
+                        tracing::error!("Could not render the forbidden page: {:?}", err);
+                        StatusCode::FORBIDDEN.into_response()
+                    }
+                }
+            },
+            Self::Repo(err) => err.into_response(),
+        }
+    }
+}
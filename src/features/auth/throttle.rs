@@ -0,0 +1,194 @@
+use askama::Template;
+
+use crate::{
+    config::LoginThrottleConfig,
+    features::auth::error::AuthError,
+    repo::infra::{login_throttle::{FailedAttempts, LoginThrottleRepo, ThrottleScope}, user::UserRepo},
+    web_service::server::ServerState,
+};
+
+
+#[derive(Template)]
+#[template(path = "auth/emails/account_locked.html")]
+pub struct AccountLockedEmailTemplate {
+    reset_link: String,
+}
+
+// emails longer than `users.email` can't belong to an account, they only have to fit the counter
+const MAX_KEY_LENGTH: usize = 100;
+// past this many doublings the lockout is capped by the window anyway
+const MAX_DOUBLINGS: usize = 16;
+
+/// Emails are compared case insensitively so `A@x.com` and `a@x.com` share a counter
+pub fn key(email: &str) -> String {
+    email.trim().to_lowercase().chars().take(MAX_KEY_LENGTH).collect()
+}
+
+// seconds a key stays locked after max_failures + n failures
+fn lockout_seconds(config: &LoginThrottleConfig) -> Vec<f64> {
+    (0..=MAX_DOUBLINGS)
+        .map(|doublings| {
+            (config.lockout_minutes as f64 * 60.0 * 2f64.powi(doublings as i32))
+                .min(config.window_minutes as f64 * 60.0)
+        })
+        .collect()
+}
+
+// seconds until the next attempt is allowed, None when not locked
+fn retry_after(attempts: &FailedAttempts, max_failures: i64, config: &LoginThrottleConfig) -> Option<u64> {
+    if attempts.count < max_failures {
+        return None;
+    }
+
+    let lockouts = lockout_seconds(config);
+    let lockout = lockouts[((attempts.count - max_failures) as usize).min(MAX_DOUBLINGS)];
+    let remaining = lockout - attempts.seconds_since_last.unwrap_or(0.0);
+
+    (remaining > 0.0).then(|| remaining.ceil() as u64)
+}
+
+/// Counts the attempt as failed up front and refuses it while the account or ip is locked,
+/// so parallel guesses can't get past the limit. Attempts refused here are not counted,
+/// so nobody can keep someone else's account locked forever. Follow it with
+/// `record_failure`, `record_success` or `release`
+pub async fn check(state: &ServerState, email: &str, ip: Option<&str>) -> Result<(), AuthError> {
+    let config = &state.config.login_throttle;
+    let lockouts = lockout_seconds(config);
+
+    if let Some(ip) = ip {
+        let counted = state.repo
+            .login_throttle_attempt(ThrottleScope::Ip, ip, config.max_failures_per_ip as i32, &lockouts, config.window_minutes)
+            .await?;
+
+        if counted.is_none() {
+            return Err(too_many_attempts(state, ThrottleScope::Ip, ip, config.max_failures_per_ip).await?);
+        }
+    }
+
+    let counted = state.repo
+        .login_throttle_attempt(ThrottleScope::Account, email, config.max_failures_per_account as i32, &lockouts, config.window_minutes)
+        .await?;
+
+    if counted.is_none() {
+        if let Some(ip) = ip {
+            state.repo.login_throttle_refund(ThrottleScope::Ip, ip).await?;
+        }
+        return Err(too_many_attempts(state, ThrottleScope::Account, email, config.max_failures_per_account).await?);
+    }
+
+    Ok(())
+}
+
+async fn too_many_attempts(state: &ServerState, scope: ThrottleScope, key: &str, max_failures: i64) -> Result<AuthError, AuthError> {
+    let config = &state.config.login_throttle;
+    let attempts = state.repo.login_throttle_failures(scope, key, config.window_minutes).await?;
+
+    // the lock may have run out in between, the next attempt gets through then
+    let retry_after_seconds = retry_after(&attempts, max_failures, config).unwrap_or(1);
+
+    Ok(AuthError::TooManyAttempts { retry_after_seconds })
+}
+
+/// The attempt `check` counted failed, the owner is told when it is the one that locked the account
+pub async fn record_failure(state: &ServerState, email: &str) -> Result<(), AuthError> {
+    let config = &state.config.login_throttle;
+
+    let account = state.repo.login_throttle_failures(ThrottleScope::Account, email, config.window_minutes).await?;
+    if account.count == config.max_failures_per_account {
+        notify_locked(state, email).await?;
+    }
+
+    Ok(())
+}
+
+/// The attempt `check` counted succeeded, which clears the account's failures. The ip only
+/// gets this attempt back so an attacker can't clear it by signing in to their own account
+pub async fn record_success(state: &ServerState, email: &str, ip: Option<&str>) -> Result<(), AuthError> {
+    state.repo.login_throttle_reset(ThrottleScope::Account, email).await?;
+
+    if let Some(ip) = ip {
+        state.repo.login_throttle_refund(ThrottleScope::Ip, ip).await?;
+    }
+
+    Ok(())
+}
+
+/// Takes back the attempt `check` counted while it is still undecided, e.g. the password
+/// was right but the second factor is still missing
+pub async fn release(state: &ServerState, email: &str, ip: Option<&str>) -> Result<(), AuthError> {
+    state.repo.login_throttle_refund(ThrottleScope::Account, email).await?;
+
+    if let Some(ip) = ip {
+        state.repo.login_throttle_refund(ThrottleScope::Ip, ip).await?;
+    }
+
+    Ok(())
+}
+
+async fn notify_locked(state: &ServerState, email: &str) -> Result<(), AuthError> {
+    let Some(user) = state.repo.user_get_by_email(email).await? else {
+        return Ok(());
+    };
+
+    let body = AccountLockedEmailTemplate {
+        reset_link: format!("{}/auth/reset-password", state.config.app.origin.trim_end_matches('/')),
+    }
+    .render()?;
+
+    let message = state.mailer.create_message(body, user.email, user.name, "Sign in attempts blocked".to_string())?;
+    // the failed attempt shouldn't wait on the smtp server
+    state.mailer.send_in_background(message);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LoginThrottleConfig {
+        LoginThrottleConfig {
+            max_failures_per_account: 5,
+            max_failures_per_ip: 50,
+            window_minutes: 60,
+            lockout_minutes: 1,
+        }
+    }
+
+    fn attempts(count: i64, seconds_since_last: f64) -> FailedAttempts {
+        FailedAttempts { count, seconds_since_last: Some(seconds_since_last) }
+    }
+
+    #[test]
+    fn not_locked_below_the_limit() {
+        assert_eq!(retry_after(&attempts(4, 0.0), 5, &config()), None);
+        assert_eq!(retry_after(&FailedAttempts::default(), 5, &config()), None);
+    }
+
+    #[test]
+    fn locked_for_the_rest_of_the_lockout() {
+        assert_eq!(retry_after(&attempts(5, 0.0), 5, &config()), Some(60));
+        assert_eq!(retry_after(&attempts(5, 45.5), 5, &config()), Some(15));
+        assert_eq!(retry_after(&attempts(5, 60.0), 5, &config()), None);
+    }
+
+    #[test]
+    fn lockout_doubles_with_every_further_failure() {
+        assert_eq!(retry_after(&attempts(6, 0.0), 5, &config()), Some(120));
+        assert_eq!(retry_after(&attempts(7, 0.0), 5, &config()), Some(240));
+        assert_eq!(retry_after(&attempts(7, 239.0), 5, &config()), Some(1));
+    }
+
+    #[test]
+    fn lockout_never_outlasts_the_window() {
+        assert_eq!(retry_after(&attempts(12, 0.0), 5, &config()), Some(60 * 60));
+        // far past the doublings that are tracked
+        assert_eq!(retry_after(&attempts(10_000, 0.0), 5, &config()), Some(60 * 60));
+    }
+
+    #[test]
+    fn keys_ignore_case_and_fit_the_counter() {
+        assert_eq!(key("  Alice@Example.com "), "alice@example.com");
+        assert_eq!(key(&"a".repeat(500)).len(), MAX_KEY_LENGTH);
+    }
+}
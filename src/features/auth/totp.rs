@@ -0,0 +1,39 @@
+use totp_rs::{Builder, Secret, Totp};
+use uuid::Uuid;
+
+use crate::{repo::infra::totp::TotpRepo, web_service::server::ServerState};
+
+use super::error::AuthError;
+
+
+/// Generates a new random secret, base32 encoded as authenticator apps expect
+pub fn generate_secret() -> String {
+    Secret::generate().to_base32()
+}
+
+pub fn build(secret: &str, account_name: &str, issuer: &str) -> Result<Totp, AuthError> {
+    let totp = Builder::new()
+        .with_secret(Secret::try_from_base32(secret)?)
+        .with_account_name(account_name.replace(':', ""))
+        .with_issuer(Some(issuer.replace(':', "")))
+        .build()?;
+
+    Ok(totp)
+}
+
+/// Checks a code against the user's secret and consumes its time step so it cannot be replayed.
+/// Works for both enabled and pending (unconfirmed) secrets, callers decide which they accept.
+pub async fn verify_code(state: &ServerState, user_id: Uuid, code: &str) -> Result<bool, AuthError> {
+    let Some(user_totp) = state.repo.totp_get(user_id).await? else {
+        return Ok(false);
+    };
+
+    // the account name is only used for otpauth urls, not for checking codes
+    let totp = build(&user_totp.secret, "user", &state.config.app.app_name)?;
+
+    let Some(step) = totp.check_current(code.trim()) else {
+        return Ok(false);
+    };
+
+    Ok(state.repo.totp_use_step(user_id, step as i64).await?)
+}
@@ -4,5 +4,8 @@
 pub enum FeatureError {
     
     #[from]
-    Auth(super::auth::error::AuthError)
+    Auth(super::auth::error::AuthError),
+
+    #[from]
+    Organization(super::organization::error::OrganizationError)
 }
\ No newline at end of file
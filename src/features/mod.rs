@@ -1,3 +1,5 @@
+pub mod admin;
 pub mod auth;
 pub mod error;
+pub mod organization;
 pub mod user;
\ No newline at end of file
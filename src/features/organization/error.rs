@@ -0,0 +1,70 @@
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+
+use crate::{features::auth::claims::error::ClaimsError, repo::error::RepoError};
+
+
+#[derive(Debug, derive_more::From)]
+pub enum OrganizationError {
+    // no active organization, or the user is no longer a member of it
+    NotAMember,
+    // the member's role can't invite with the requested role
+    CannotInvite,
+    InvalidName,
+    // the invitation expired, was already accepted or never existed
+    InvitationInvalid,
+    // the invitation was sent to another address than the signed in account's
+    InvitationWrongAccount,
+    AlreadyAMember,
+    // invitations go by email, so the account has to prove it owns the address first
+    EmailNotVerified,
+    #[from]
+    ClaimsError(ClaimsError),
+
+    #[from]
+    Askama(askama::Error),
+
+    #[from]
+    RepoError(RepoError),
+
+    #[from]
+    Mailer(crate::mailer::error::MailerError),
+}
+
+impl IntoResponse for OrganizationError {
+    fn into_response(self) -> Response {
+        match self {
+            OrganizationError::NotAMember => {
+                (StatusCode::FORBIDDEN, "You are not a member of this organization").into_response()
+            },
+            OrganizationError::CannotInvite => {
+                (StatusCode::FORBIDDEN, "Your role can't invite people with this role").into_response()
+            },
+            OrganizationError::InvalidName => {
+                (StatusCode::BAD_REQUEST, "The name must be between 1 and 100 characters").into_response()
+            },
+            OrganizationError::InvitationInvalid => {
+                (StatusCode::BAD_REQUEST, "This invitation expired or was already used, ask for a new one").into_response()
+            },
+            OrganizationError::InvitationWrongAccount => {
+                (StatusCode::FORBIDDEN, "This invitation was sent to another email address").into_response()
+            },
+            OrganizationError::AlreadyAMember => {
+                (StatusCode::CONFLICT, "This person is already a member of the organization").into_response()
+            },
+            OrganizationError::EmailNotVerified => {
+                (StatusCode::FORBIDDEN, "Verify your email address before accepting invitations").into_response()
+            },
+            OrganizationError::ClaimsError(err) => {
+                err.into_response()
+            },
+            OrganizationError::RepoError(err) => {
+                err.into_response()
+            },
+            err => {
+                tracing::error!("An organization error occured: {:?}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error, please try again later.").into_response()
+            }
+        }
+    }
+}
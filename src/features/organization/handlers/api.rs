@@ -0,0 +1,133 @@
+use askama::Template;
+use axum::{extract::{Path, State}, response::{Html, IntoResponse, Response}, Form};
+use http::header::SET_COOKIE;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    features::{
+        auth::{claims::{authorization::AuthorizationClaim, error::ClaimsError, invitation::InvitationClaim, Claims}, current_user::CurrentUser},
+        organization::{error::OrganizationError, membership::ActiveOrganization},
+    },
+    repo::infra::{organization::{InvitationOutcome, OrgRole, OrganizationRepo, TenantOrganizationRepo, INVITATION_EXP_DAYS}, user::TenantUserRepo},
+    utils::HxRedirect,
+    web_service::server::ServerState,
+};
+
+use super::views::InvitationSent;
+
+
+#[derive(Template)]
+#[template(path = "organization/emails/invitation.html")]
+pub struct InvitationEmailTemplate {
+    organization_name: String,
+    inviter_name: String,
+    link: String,
+    expire_time: String,
+}
+
+/// Reissues the access cookie for the organization the session switched to and reloads the dashboard
+async fn switch_to(state: &ServerState, claim: AuthorizationClaim, organization_id: Uuid) -> Result<Response, OrganizationError> {
+    // `require_session` keeps personal access tokens from getting here
+    let session_id = claim.session_id.ok_or(ClaimsError::InsufficientScope)?;
+    state.repo.session_set_organization(session_id, organization_id).await?;
+
+    let cookie = claim.with_organization(organization_id).cookie()?;
+
+    Ok((
+        [(SET_COOKIE, cookie)],
+        [(HxRedirect::HEADER_NAME, AuthorizationClaim::SUCCESS_REDIRECT_URI)],
+    ).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct CreatePayload {
+    name: String,
+}
+
+pub async fn create(State(state): State<ServerState>, claim: AuthorizationClaim, Form(payload): Form<CreatePayload>) -> Result<impl IntoResponse, OrganizationError> {
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(OrganizationError::InvalidName);
+    }
+
+    let organization_id = state.repo.organization_create(claim.user_id, name).await?;
+
+    switch_to(&state, claim, organization_id).await
+}
+
+pub async fn switch(State(state): State<ServerState>, claim: AuthorizationClaim, Path(id): Path<Uuid>) -> Result<impl IntoResponse, OrganizationError> {
+    if state.repo.for_tenant(id).member_role(claim.user_id).await?.is_none() {
+        return Err(OrganizationError::NotAMember);
+    }
+
+    switch_to(&state, claim, id).await
+}
+
+#[derive(Deserialize)]
+pub struct InvitePayload {
+    email: String,
+    role: OrgRole,
+}
+
+pub async fn invite(State(state): State<ServerState>, organization: ActiveOrganization, Form(payload): Form<InvitePayload>) -> Result<impl IntoResponse, OrganizationError> {
+    if !organization.role.can_invite(payload.role) {
+        return Err(OrganizationError::CannotInvite);
+    }
+
+    let email = payload.email.trim().to_string();
+
+    // the organization only sees its own members, anyone else is worth an invitation
+    if organization.repo.user_get_by_email(&email).await?.is_some() {
+        return Err(OrganizationError::AlreadyAMember);
+    }
+
+    let invitation_id = organization.repo
+        .invitation_create(&email, payload.role, organization.claim.user_id)
+        .await?;
+
+    let invitation = state.repo
+        .invitation_get(invitation_id)
+        .await?
+        .ok_or(OrganizationError::InvitationInvalid)?;
+
+    let body = InvitationEmailTemplate {
+        organization_name: invitation.organization_name.clone(),
+        inviter_name: invitation.inviter_name.unwrap_or_else(|| "Someone".to_string()),
+        link: format!(
+            "{}/orgs/invitations/accept?token={}",
+            state.config.app.origin.trim_end_matches('/'),
+            InvitationClaim::new(invitation_id).token()?
+        ),
+        expire_time: INVITATION_EXP_DAYS.to_string(),
+    }
+    .render()?;
+
+    let subject = format!("You are invited to join {}", invitation.organization_name);
+    let message = state.mailer.create_message(body, email.clone(), email.clone(), subject)?;
+    // the invitation is stored either way, a slow smtp server shouldn't hold up the response
+    state.mailer.send_in_background(message);
+
+    Ok(Html(InvitationSent { email }.render()?))
+}
+
+#[derive(Deserialize)]
+pub struct AcceptPayload {
+    token: String,
+}
+
+pub async fn accept(State(state): State<ServerState>, CurrentUser { claim, user }: CurrentUser, Form(payload): Form<AcceptPayload>) -> Result<impl IntoResponse, OrganizationError> {
+    let invitation_claim = InvitationClaim::from_token(payload.token)
+        .map_err(|_| OrganizationError::InvitationInvalid)?;
+
+    // otherwise registering with someone else's address would be enough to take their invitation
+    if !user.is_email_verified() {
+        return Err(OrganizationError::EmailNotVerified);
+    }
+
+    match state.repo.invitation_accept(invitation_claim.jti, user.id, &user.email).await? {
+        InvitationOutcome::Joined { organization_id } => switch_to(&state, claim, organization_id).await,
+        InvitationOutcome::WrongAccount => Err(OrganizationError::InvitationWrongAccount),
+        InvitationOutcome::Invalid => Err(OrganizationError::InvitationInvalid),
+    }
+}
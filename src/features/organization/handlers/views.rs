@@ -0,0 +1,135 @@
+use askama::Template;
+use axum::{extract::{Query, State}, response::Html};
+use serde::Deserialize;
+
+use crate::{
+    features::auth::{claims::{authorization::AuthorizationClaim, invitation::InvitationClaim, Claims}, current_user::CurrentUser},
+    repo::{error::RepoError, infra::organization::{OrgRole, OrganizationRepo, TenantOrganizationRepo}},
+    web_service::server::ServerState,
+    ServerError,
+};
+
+
+pub struct MembershipRow {
+    pub id: String,
+    pub name: String,
+    pub role: &'static str,
+    pub is_active: bool,
+}
+
+pub struct MemberRow {
+    pub name: String,
+    pub email: String,
+    pub role: &'static str,
+}
+
+pub struct ActiveOrganizationDetails {
+    pub name: String,
+    pub role: &'static str,
+    pub members: Vec<MemberRow>,
+    pub can_invite: bool,
+    pub can_invite_admins: bool,
+}
+
+/// The organization switcher on the dashboard, with the members of the active one
+#[derive(Template)]
+#[template(path = "organization/fragments/organizations.html")]
+pub struct OrganizationsFragment {
+    pub memberships: Vec<MembershipRow>,
+    pub active: Option<ActiveOrganizationDetails>,
+}
+
+impl OrganizationsFragment {
+    pub async fn load(state: &ServerState, claim: &AuthorizationClaim) -> Result<Self, RepoError> {
+        let memberships = state.repo.membership_list_for_user(claim.user_id).await?;
+
+        // the claim can be a few minutes behind, only trust it while the membership still exists
+        let active = memberships
+            .iter()
+            .find(|membership| Some(membership.organization_id) == claim.organization_id);
+
+        let active = match active {
+            Some(membership) => {
+                let members = state.repo
+                    .for_tenant(membership.organization_id)
+                    .members()
+                    .await?
+                    .into_iter()
+                    .map(|member| MemberRow { name: member.name, email: member.email, role: member.role.as_str() })
+                    .collect();
+
+                Some(ActiveOrganizationDetails {
+                    name: membership.organization_name.clone(),
+                    role: membership.role.as_str(),
+                    members,
+                    can_invite: membership.role.can_invite(OrgRole::Member),
+                    can_invite_admins: membership.role.can_invite(OrgRole::Admin),
+                })
+            },
+            None => None,
+        };
+
+        let memberships = memberships
+            .into_iter()
+            .map(|membership| MembershipRow {
+                id: membership.organization_id.to_string(),
+                is_active: Some(membership.organization_id) == claim.organization_id,
+                name: membership.organization_name,
+                role: membership.role.as_str(),
+            })
+            .collect();
+
+        Ok(Self { memberships, active })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "organization/fragments/invitation_sent.html")]
+pub struct InvitationSent {
+    pub email: String,
+}
+
+pub struct InvitationDetails {
+    organization_name: String,
+    inviter_name: String,
+    email: String,
+}
+
+/// Where invitation links land, joining takes a click so link scanners in mail clients can't accept
+#[derive(Template)]
+#[template(path = "organization/invitation.html")]
+pub struct InvitationTemplate {
+    token: String,
+    invitation: Option<InvitationDetails>,
+    signed_in_email: Option<String>,
+    wrong_account: bool,
+}
+
+#[derive(Deserialize)]
+pub struct InvitationQuery {
+    token: String,
+}
+
+impl InvitationTemplate {
+    pub async fn handler(State(state): State<ServerState>, current_user: Option<CurrentUser>, Query(query): Query<InvitationQuery>) -> Result<Html<String>, ServerError> {
+        let invitation = match InvitationClaim::from_token(query.token.clone()) {
+            Ok(invitation_claim) => state.repo.invitation_get(invitation_claim.jti).await?,
+            Err(_) => None,
+        };
+
+        let signed_in_email = current_user.map(|current_user| current_user.user.email);
+
+        let wrong_account = match (&invitation, &signed_in_email) {
+            (Some(invitation), Some(email)) => !invitation.email.eq_ignore_ascii_case(email),
+            _ => false,
+        };
+
+        let invitation = invitation.map(|invitation| InvitationDetails {
+            organization_name: invitation.organization_name,
+            inviter_name: invitation.inviter_name.unwrap_or_else(|| "Someone".to_string()),
+            email: invitation.email,
+        });
+
+        Ok(Html(Self { token: query.token, invitation, signed_in_email, wrong_account }.render()?))
+    }
+}
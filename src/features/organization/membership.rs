@@ -0,0 +1,44 @@
+use axum::extract::{FromRef, FromRequestParts};
+use http::request::Parts;
+use uuid::Uuid;
+
+use crate::{
+    features::auth::claims::authorization::AuthorizationClaim,
+    repo::{infra::organization::{OrgRole, TenantOrganizationRepo}, tenant::TenantRepository},
+    web_service::server::ServerState,
+};
+
+use super::error::OrganizationError;
+
+
+/// The signed in user's active organization and their role in it.
+/// The organization comes from the claim, the membership is checked against the database every time.
+/// `repo` only ever sees this organization's rows, use it for anything the organization owns
+pub struct ActiveOrganization {
+    pub claim: AuthorizationClaim,
+    pub organization_id: Uuid,
+    pub role: OrgRole,
+    pub repo: TenantRepository,
+}
+
+impl<S> FromRequestParts<S> for ActiveOrganization
+where
+    ServerState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = OrganizationError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claim = AuthorizationClaim::from_request_parts(parts, state).await?;
+        let organization_id = claim.organization_id.ok_or(OrganizationError::NotAMember)?;
+
+        let state = ServerState::from_ref(state);
+        let repo = state.repo.for_tenant(organization_id);
+        let role = repo
+            .member_role(claim.user_id)
+            .await?
+            .ok_or(OrganizationError::NotAMember)?;
+
+        Ok(Self { claim, organization_id, role, repo })
+    }
+}
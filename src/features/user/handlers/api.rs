@@ -0,0 +1,38 @@
+use askama::Template;
+use axum::{extract::{Path, State}, response::{Html, IntoResponse}};
+use uuid::Uuid;
+
+use crate::{features::auth::claims::authorization::{AuthorizationClaim, RequireSession}, repo::infra::session::SessionRepo, web_service::server::ServerState, ServerError};
+
+use super::views::{SessionRow, SessionsFragment};
+
+
+async fn render_sessions(state: &ServerState, claim: &AuthorizationClaim) -> Result<Html<String>, ServerError> {
+    let sessions = state.repo
+        .session_list_active_for_user(claim.user_id)
+        .await?
+        .into_iter()
+        .map(|session| SessionRow::new(session, claim.session_id))
+        .collect();
+
+    Ok(Html(SessionsFragment { sessions }.render()?))
+}
+
+pub async fn sessions(State(state): State<ServerState>, claim: AuthorizationClaim) -> Result<impl IntoResponse, ServerError> {
+    render_sessions(&state, &claim).await
+}
+
+pub async fn revoke_session(State(state): State<ServerState>, RequireSession { claim, session_id }: RequireSession, Path(id): Path<Uuid>) -> Result<impl IntoResponse, ServerError> {
+    // the current session is signed out with the regular logout button
+    if id != session_id {
+        state.repo.session_revoke_for_user(id, claim.user_id).await?;
+    }
+
+    render_sessions(&state, &claim).await
+}
+
+pub async fn revoke_other_sessions(State(state): State<ServerState>, RequireSession { claim, session_id }: RequireSession) -> Result<impl IntoResponse, ServerError> {
+    state.repo.session_revoke_others(claim.user_id, session_id).await?;
+
+    render_sessions(&state, &claim).await
+}
@@ -1 +1,2 @@
-pub mod views;
\ No newline at end of file
+pub mod api;
+pub mod views;
@@ -2,7 +2,7 @@ use askama::Template;
 use axum::{extract::State, response::{Html, IntoResponse}};
 use http::StatusCode;
 
-use crate::{features::auth::claims::authorization::AuthorizationClaim, repo::infra::user::UserRepo, web_service::server::ServerState, ServerError};
+use crate::{config::UnverifiedEmailPolicy, features::{auth::{current_user::{CurrentUser, MaybeUser}, roles::{Admin, Role}, handlers::views::{passkey::PasskeyRow, personal_access_token::AccessTokenRow, two_factor::TwoFactorStatus}}, organization::handlers::views::OrganizationsFragment}, repo::infra::{personal_access_token::PersonalAccessTokenRepo, recovery_code::RecoveryCodeRepo, session::{Session, SessionRepo}, totp::TotpRepo, webauthn::WebauthnRepo}, web_service::server::ServerState, ServerError};
 
 
 
@@ -10,30 +10,86 @@ use crate::{features::auth::claims::authorization::AuthorizationClaim, repo::inf
 #[template(path = "user/dashboard.html")]
 pub struct DashboardTemplate {
     email: String,
-    name: String
+    name: String,
+    sessions: Vec<SessionRow>,
+    // pre-rendered so the fragment's own fields dont leak into this template
+    two_factor_status: String,
+    organizations: String,
+    passkeys: Vec<PasskeyRow>,
+    access_tokens: Vec<AccessTokenRow>,
+    new_access_token: Option<String>,
+    email_verified: bool,
+    // only for showing the link, the admin pages check the role themselves
+    is_admin: bool,
+}
 
+/// Shown instead of the dashboard when the unverified email policy is `block`
+#[derive(Template)]
+#[template(path = "user/verify_email.html")]
+pub struct VerifyEmailTemplate {
+    email: String,
 }
 
 
 impl DashboardTemplate {
 
-    pub async fn handler(State(state): State<ServerState>, claim: AuthorizationClaim) -> Result<impl IntoResponse, ServerError> {
-        let user = state.repo.user_get_by_id(claim.user_id).await?;
-
-        if user.is_none() {
+    pub async fn handler(State(state): State<ServerState>, CurrentUser { claim, user }: CurrentUser) -> Result<impl IntoResponse, ServerError> {
+        if !user.is_email_verified() && state.config.app.unverified_email_policy == UnverifiedEmailPolicy::Block {
             return Ok((
-                [(http::header::LOCATION, "/auth/login")],
-                StatusCode::UNAUTHORIZED
+                StatusCode::OK,
+                Html(VerifyEmailTemplate { email: user.email }.render()?)
             ).into_response());
         }
 
-        let user = user.unwrap();
+        let sessions = state.repo
+            .session_list_active_for_user(user.id)
+            .await?
+            .into_iter()
+            .map(|session| SessionRow::new(session, claim.session_id))
+            .collect();
+
+        let two_factor_enabled = state.repo
+            .totp_get(user.id)
+            .await?
+            .is_some_and(|t| t.is_enabled());
+
+        let two_factor_status = if two_factor_enabled {
+            let remaining = state.repo.recovery_codes_remaining(user.id).await?;
+            TwoFactorStatus::enabled(remaining, vec![])
+        } else {
+            TwoFactorStatus::disabled()
+        }
+        .render()?;
+
+        let organizations = OrganizationsFragment::load(&state, &claim).await?.render()?;
+
+        let passkeys = state.repo
+            .webauthn_credentials_for_user(user.id)
+            .await?
+            .into_iter()
+            .map(PasskeyRow::from)
+            .collect();
+
+        let access_tokens = state.repo
+            .personal_access_token_list_for_user(user.id)
+            .await?
+            .into_iter()
+            .map(AccessTokenRow::from)
+            .collect();
 
         Ok((
             StatusCode::OK,
             Html(Self {
+                email_verified: user.is_email_verified(),
+                is_admin: claim.has_role(Admin::NAME),
                 email: user.email,
-                name: user.name
+                name: user.name,
+                sessions,
+                two_factor_status,
+                organizations,
+                passkeys,
+                access_tokens,
+                new_access_token: None,
             }
             .render()?)
         ).into_response())
@@ -41,16 +97,72 @@ impl DashboardTemplate {
 }
 
 
+pub struct SessionRow {
+    pub id: String,
+    pub device: String,
+    pub ip: String,
+    pub last_seen: String,
+    pub is_current: bool,
+}
+
+impl SessionRow {
+    pub fn new(session: Session, current_session_id: Option<uuid::Uuid>) -> Self {
+        Self {
+            id: session.id.to_string(),
+            device: describe_device(session.user_agent.as_deref()),
+            ip: session.ip.unwrap_or_else(|| "Unknown".to_string()),
+            last_seen: session.last_seen.format("%Y-%m-%d %H:%M").to_string(),
+            is_current: current_session_id == Some(session.id),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "user/fragments/sessions.html")]
+pub struct SessionsFragment {
+    pub sessions: Vec<SessionRow>,
+}
+
+// turns a user agent into something like "Firefox on Windows", good enough to recognize a device
+fn describe_device(user_agent: Option<&str>) -> String {
+    let Some(ua) = user_agent else {
+        return "Unknown device".to_string();
+    };
+
+    // order matters, most browsers also claim to be chrome/safari
+    let browser = [("Edg/", "Edge"), ("OPR/", "Opera"), ("Firefox/", "Firefox"), ("Chrome/", "Chrome"), ("Safari/", "Safari")]
+        .iter()
+        .find(|(needle, _)| ua.contains(needle))
+        .map(|(_, name)| *name);
+
+    let os = [("Windows", "Windows"), ("iPhone", "iOS"), ("iPad", "iOS"), ("Android", "Android"), ("Mac OS X", "macOS"), ("Linux", "Linux")]
+        .iter()
+        .find(|(needle, _)| ua.contains(needle))
+        .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(browser), None) => browser.to_string(),
+        (None, Some(os)) => os.to_string(),
+        (None, None) => ua.chars().take(60).collect(),
+    }
+}
+
+
 #[derive(Template)]
 #[template(path = "index.html")]
 pub struct IndexTemplate {
+    // None for visitors who are not signed in
+    name: Option<String>,
 }
 
 impl IndexTemplate {
-    pub async fn handler() -> Result<impl IntoResponse, ServerError> {
+    pub async fn handler(MaybeUser(current_user): MaybeUser) -> Result<impl IntoResponse, ServerError> {
+        let name = current_user.map(|current_user| current_user.user.name);
+
         Ok((
             StatusCode::OK,
-            Html(Self {}.render()?)
+            Html(Self { name }.render()?)
         ).into_response())
     }
 }
\ No newline at end of file
@@ -8,6 +8,7 @@ pub mod config;
 pub mod repo;
 pub mod web_service;
 pub mod mailer;
+pub mod rate_limit;
 
 #[derive(derive_more::From, Debug)]
 pub enum ServerError {
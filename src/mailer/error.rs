@@ -16,9 +16,7 @@ pub enum MailerError {
 impl IntoResponse for MailerError {
     fn into_response(self) -> Response {
         tracing::error!("MESSAGING: an error occured: {:?}", self);
-        match self { 
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, "An error occured sending an email").into_response()
-        }
+        (StatusCode::INTERNAL_SERVER_ERROR, "An error occured sending an email").into_response()
 
     }
     
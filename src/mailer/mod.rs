@@ -37,6 +37,17 @@ impl Mailer {
     } 
 
 
+    /// Sends without making the caller wait on the smtp server, failures are only logged
+    pub fn send_in_background(&self, message: Message) {
+        let mailer = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = mailer.send_message(message) {
+                tracing::error!("Could not send email: {:?}", err);
+            }
+        });
+    }
+
     pub fn create_message(&self, body: String, reciever_email: String, reciever_name: String,subject: String) -> Result<Message,  MailerError> {
         let message = Message::builder()
             .from(Mailbox::new(Some(self.sender_name.clone()), self.noreply_email.clone()))
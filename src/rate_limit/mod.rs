@@ -0,0 +1,160 @@
+//! Token bucket rate limiting for groups of api routes, applied with `route_layer` so only
+//! requests that match a route in the group use up its tokens.
+
+pub mod store;
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use askama::Template;
+use axum::{
+    body::Body,
+    extract::FromRequestParts,
+    http::{header, Request, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use serde::Deserialize;
+use store::RateLimitStore;
+use tower::{Layer, Service};
+
+use crate::{
+    features::auth::claims::{authorization::AuthorizationClaim, Claims},
+    utils::{extract_cookie_value, ClientInfo},
+    web_service::server::ServerState,
+};
+
+
+/// A bucket holds `burst` requests and refills at `per_minute`
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct RateLimitPolicy {
+    pub burst: u32,
+    pub per_minute: f64,
+}
+
+/// What requests share a bucket
+#[derive(Debug, Clone, Copy)]
+pub enum RateLimitKey {
+    Ip,
+    /// The signed in user, or the ip for anonymous requests
+    UserOrIp,
+}
+
+#[derive(Template)]
+#[template(path = "fragments/too_many_requests.html")]
+struct TooManyRequests {
+    retry_after_seconds: u64,
+}
+
+struct Limiter {
+    group: &'static str,
+    policy: RateLimitPolicy,
+    key: RateLimitKey,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl Limiter {
+    async fn key_for(&self, parts: &mut axum::http::request::Parts) -> String {
+        if let RateLimitKey::UserOrIp = self.key {
+            // only the signature is checked, this runs before the session is looked up
+            let user_id = parts
+                .headers
+                .get(header::COOKIE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|cookies| extract_cookie_value(cookies, &AuthorizationClaim::cookie_name()))
+                .and_then(|token| AuthorizationClaim::from_token(token).ok())
+                .map(|claim| claim.user_id);
+
+            if let Some(user_id) = user_id {
+                return format!("{}:user:{}", self.group, user_id);
+            }
+        }
+
+        let Ok(client) = ClientInfo::from_request_parts(parts, &()).await;
+        format!("{}:ip:{}", self.group, client.ip.unwrap_or_default())
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<Limiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(group: &'static str, policy: RateLimitPolicy, key: RateLimitKey, state: &ServerState) -> Self {
+        Self {
+            limiter: Arc::new(Limiter {
+                group,
+                policy,
+                key,
+                store: state.rate_limit_store.clone(),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit { inner, limiter: self.limiter.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<Limiter>,
+}
+
+impl<S> Service<Request<Body>> for RateLimit<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // the clone may not be ready, keep the one that was polled
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            let key = limiter.key_for(&mut parts).await;
+
+            match limiter.store.take(&key, &limiter.policy).await {
+                Ok(()) => inner.call(Request::from_parts(parts, body)).await,
+                Err(retry_after) => {
+                    tracing::debug!("Rate limited {}", key);
+                    Ok(too_many_requests(retry_after))
+                }
+            }
+        })
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let retry_after_seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let body = TooManyRequests { retry_after_seconds }
+        .render()
+        .unwrap_or_else(|_| "Too many requests, please try again later".to_string());
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_seconds.to_string())],
+        Html(body),
+    ).into_response()
+}
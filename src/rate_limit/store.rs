@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::RateLimitPolicy;
+
+/// Where token buckets are kept, implement this to share limits between several instances
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Takes a token from the bucket under `key`, or returns how long until one is available
+    async fn take(&self, key: &str, policy: &RateLimitPolicy) -> Result<(), Duration>;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    // kept with the bucket since several policies share the store
+    capacity: f64,
+    refill_per_second: f64,
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant) -> f64 {
+        (self.tokens + now.duration_since(self.updated).as_secs_f64() * self.refill_per_second).min(self.capacity)
+    }
+}
+
+/// Buckets in process memory, limits reset when the server restarts
+#[derive(Default)]
+pub struct InMemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl InMemoryStore {
+    // past this many buckets the idle ones are dropped
+    const PRUNE_THRESHOLD: usize = 10_000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn take_at(&self, key: &str, policy: &RateLimitPolicy, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= Self::PRUNE_THRESHOLD {
+            // a bucket that would be full again holds no information
+            buckets.retain(|_, bucket| bucket.refilled(now) < bucket.capacity);
+        }
+
+        let bucket = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket {
+                tokens: policy.burst as f64,
+                updated: now,
+                capacity: policy.burst as f64,
+                refill_per_second: policy.per_minute / 60.0,
+            });
+
+        bucket.tokens = bucket.refilled(now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        if bucket.refill_per_second <= 0.0 {
+            return Err(Duration::from_secs(60));
+        }
+
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.refill_per_second))
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for InMemoryStore {
+    async fn take(&self, key: &str, policy: &RateLimitPolicy) -> Result<(), Duration> {
+        self.take_at(key, policy, Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(burst: u32, per_minute: f64) -> RateLimitPolicy {
+        RateLimitPolicy { burst, per_minute }
+    }
+
+    #[test]
+    fn allows_a_burst_then_refuses() {
+        let store = InMemoryStore::new();
+        let now = Instant::now();
+        let policy = policy(3, 60.0);
+
+        for _ in 0..3 {
+            assert!(store.take_at("ip:1", &policy, now).is_ok());
+        }
+
+        // one token a second
+        assert_eq!(store.take_at("ip:1", &policy, now), Err(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn refills_over_time_up_to_the_burst() {
+        let store = InMemoryStore::new();
+        let now = Instant::now();
+        let policy = policy(2, 60.0);
+
+        store.take_at("ip:1", &policy, now).unwrap();
+        store.take_at("ip:1", &policy, now).unwrap();
+        assert!(store.take_at("ip:1", &policy, now + Duration::from_millis(500)).is_err());
+        assert!(store.take_at("ip:1", &policy, now + Duration::from_secs(1)).is_ok());
+
+        // a long pause doesn't save up more than the burst
+        let later = now + Duration::from_secs(600);
+        assert!(store.take_at("ip:1", &policy, later).is_ok());
+        assert!(store.take_at("ip:1", &policy, later).is_ok());
+        assert!(store.take_at("ip:1", &policy, later).is_err());
+    }
+
+    #[test]
+    fn keys_have_their_own_buckets() {
+        let store = InMemoryStore::new();
+        let now = Instant::now();
+        let policy = policy(1, 1.0);
+
+        assert!(store.take_at("ip:1", &policy, now).is_ok());
+        assert!(store.take_at("ip:1", &policy, now).is_err());
+        assert!(store.take_at("ip:2", &policy, now).is_ok());
+    }
+
+    #[test]
+    fn a_policy_without_refill_waits_a_minute() {
+        let store = InMemoryStore::new();
+        let now = Instant::now();
+        let policy = policy(1, 0.0);
+
+        store.take_at("ip:1", &policy, now).unwrap();
+        assert_eq!(store.take_at("ip:1", &policy, now + Duration::from_secs(3600)), Err(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn pruning_judges_each_bucket_by_its_own_policy() {
+        let store = InMemoryStore::new();
+        let now = Instant::now();
+        let slow = policy(1, 1.0);
+        let fast = policy(1, 6000.0);
+
+        store.take_at("email:1", &slow, now).unwrap();
+        for i in 1..InMemoryStore::PRUNE_THRESHOLD {
+            store.take_at(&format!("api:{i}"), &fast, now).unwrap();
+        }
+
+        // the fast buckets are full again a second later, the slow one is not
+        store.take_at("api:0", &fast, now + Duration::from_secs(1)).unwrap();
+
+        let buckets = store.buckets.lock().unwrap();
+        assert!(buckets.contains_key("email:1"));
+        assert!(!buckets.contains_key("api:1"));
+    }
+}
@@ -9,6 +9,8 @@ pub enum RepoError {
         body: Html<String>
     },
     EmailNotFound,
+    // the password reset was already used, expired or never had its code entered
+    PasswordResetInvalid,
 
     #[from]
     Sqlx(sqlx::Error),
@@ -29,6 +31,9 @@ impl IntoResponse for RepoError {
             RepoError::EmailNotFound => {
                 (StatusCode::NOT_FOUND, "Email not found").into_response()
             },
+            RepoError::PasswordResetInvalid => {
+                (StatusCode::UNAUTHORIZED, "This password reset is no longer valid, please request a new code").into_response()
+            },
             RepoError::ValidationError { body } => {
                 (StatusCode::BAD_REQUEST, body).into_response()
             },
@@ -0,0 +1,196 @@
+// CREATE TABLE IF NOT EXISTS email_changes (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     old_email VARCHAR(100) NOT NULL,
+//     new_email VARCHAR(100) NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP NOT NULL,
+//     confirmed_at TIMESTAMP,
+//     reverted_at TIMESTAMP
+// );
+
+use uuid::Uuid;
+
+use crate::repo::utils::{is_unique_violation, is_valid_email};
+
+use super::super::error::RepoError;
+
+pub enum EmailChangeOutcome {
+    /// `users.email` was updated for this user
+    Changed { user_id: Uuid, email: String },
+    /// Another account took the address after the change was requested
+    EmailTaken,
+    /// The change does not exist, expired, was already applied or was reverted
+    Invalid,
+}
+
+#[async_trait::async_trait]
+pub trait EmailChangeRepo {
+    /// Records a pending change, replacing any other pending change of the user
+    async fn email_change_create(&self, user_id: Uuid, old_email: &str, new_email: &str) -> Result<Uuid, RepoError>;
+    /// Swaps the user's email to the new address, only while it still is the old one
+    async fn email_change_confirm(&self, id: Uuid) -> Result<EmailChangeOutcome, RepoError>;
+    /// Cancels a pending change, or puts a confirmed one back to the old address whatever the email
+    /// was changed to since. Later changes are cancelled with it and the user is signed out everywhere
+    async fn email_change_revert(&self, id: Uuid) -> Result<EmailChangeOutcome, RepoError>;
+}
+
+pub const EMAIL_CHANGE_EXP_HOURS: i32 = 24;
+
+#[async_trait::async_trait]
+impl EmailChangeRepo for super::super::Repository {
+    async fn email_change_create(&self, user_id: Uuid, old_email: &str, new_email: &str) -> Result<Uuid, RepoError> {
+        is_valid_email(new_email)?;
+
+        let id = Uuid::new_v4();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM email_changes WHERE user_id = $1 AND confirmed_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO email_changes (id, user_id, old_email, new_email, expires_at)
+            VALUES ($1, $2, $3, $4, NOW() + make_interval(hours => $5))
+            ",
+            id,
+            user_id,
+            old_email,
+            new_email,
+            EMAIL_CHANGE_EXP_HOURS
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn email_change_confirm(&self, id: Uuid) -> Result<EmailChangeOutcome, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(change) = sqlx::query!(
+            "
+            SELECT user_id, old_email, new_email FROM email_changes
+            WHERE id = $1 AND confirmed_at IS NULL AND reverted_at IS NULL AND expires_at > NOW()
+            FOR UPDATE
+            ",
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await? else {
+            return Ok(EmailChangeOutcome::Invalid);
+        };
+
+        // the new address was proven by following the link, so it counts as verified
+        let updated = sqlx::query!(
+            "UPDATE users SET email = $1, email_verified_at = NOW() WHERE id = $2 AND email = $3",
+            change.new_email,
+            change.user_id,
+            change.old_email
+        )
+        .execute(&mut *tx)
+        .await;
+
+        match updated {
+            Ok(result) if result.rows_affected() == 0 => return Ok(EmailChangeOutcome::Invalid),
+            Ok(_) => {},
+            Err(err) if is_unique_violation(&err) => return Ok(EmailChangeOutcome::EmailTaken),
+            Err(err) => return Err(err.into()),
+        }
+
+        sqlx::query!(
+            "UPDATE email_changes SET confirmed_at = NOW() WHERE id = $1",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(EmailChangeOutcome::Changed { user_id: change.user_id, email: change.new_email })
+    }
+
+    async fn email_change_revert(&self, id: Uuid) -> Result<EmailChangeOutcome, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(change) = sqlx::query!(
+            r#"
+            SELECT user_id, old_email, created_at, confirmed_at IS NOT NULL AS "confirmed!" FROM email_changes
+            WHERE id = $1 AND reverted_at IS NULL
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await? else {
+            return Ok(EmailChangeOutcome::Invalid);
+        };
+
+        if change.confirmed {
+            // keyed on the user rather than the address it was changed to, so chaining
+            // another change onto the hijacked address doesn't make this link useless
+            let updated = sqlx::query!(
+                "UPDATE users SET email = $1, email_verified_at = NOW() WHERE id = $2",
+                change.old_email,
+                change.user_id
+            )
+            .execute(&mut *tx)
+            .await;
+
+            match updated {
+                Ok(result) if result.rows_affected() == 0 => return Ok(EmailChangeOutcome::Invalid),
+                Ok(_) => {},
+                Err(err) if is_unique_violation(&err) => return Ok(EmailChangeOutcome::EmailTaken),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        // revert links of later changes went to addresses the owner never had
+        sqlx::query!(
+            "
+            UPDATE email_changes SET reverted_at = NOW()
+            WHERE user_id = $1 AND reverted_at IS NULL AND (id = $2 OR created_at >= $3)
+            ",
+            change.user_id,
+            id,
+            change.created_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // the change was not wanted, so whoever made it loses every way back in
+        sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            change.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "
+            UPDATE refresh_tokens SET expires_at = NOW()
+            WHERE expires_at > NOW() AND session_id IN (SELECT id FROM sessions WHERE user_id = $1)
+            ",
+            change.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE personal_access_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            change.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(EmailChangeOutcome::Changed { user_id: change.user_id, email: change.old_email })
+    }
+}
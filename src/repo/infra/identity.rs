@@ -0,0 +1,104 @@
+// CREATE TABLE IF NOT EXISTS user_identities (
+//     provider VARCHAR(50) NOT NULL,
+//     subject VARCHAR(255) NOT NULL,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     email VARCHAR(100),
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     PRIMARY KEY (provider, subject)
+// );
+
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+#[async_trait::async_trait]
+pub trait IdentityRepo {
+    /// Returns the user linked to an external account, `subject` is the provider's `sub` claim
+    async fn identity_get_user_id(&self, provider: &str, subject: &str) -> Result<Option<Uuid>, RepoError>;
+    async fn identity_link(&self, provider: &str, subject: &str, user_id: Uuid, email: Option<&str>) -> Result<(), RepoError>;
+    /// Hands an account whose email was never verified to the provider verified owner of that email.
+    /// Whatever the registrant set up to sign in is removed, returns false if the email was verified already
+    async fn identity_take_over_unverified(&self, user_id: Uuid) -> Result<bool, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl IdentityRepo for super::super::Repository {
+    async fn identity_get_user_id(&self, provider: &str, subject: &str) -> Result<Option<Uuid>, RepoError> {
+        let user_id = sqlx::query_scalar!(
+            "SELECT user_id FROM user_identities WHERE provider = $1 AND subject = $2",
+            provider,
+            subject
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    async fn identity_link(&self, provider: &str, subject: &str, user_id: Uuid, email: Option<&str>) -> Result<(), RepoError> {
+        sqlx::query!(
+            "
+            INSERT INTO user_identities (provider, subject, user_id, email) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (provider, subject) DO NOTHING
+            ",
+            provider,
+            subject,
+            user_id,
+            email
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn identity_take_over_unverified(&self, user_id: Uuid) -> Result<bool, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let taken = sqlx::query_scalar!(
+            "
+            UPDATE users SET password_hash = NULL, email_verified_at = NOW()
+            WHERE id = $1 AND email_verified_at IS NULL
+            RETURNING id
+            ",
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if taken.is_none() {
+            return Ok(false);
+        }
+
+        sqlx::query!("DELETE FROM webauthn_credentials WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // a second factor the registrant enrolled would lock the real owner out
+        sqlx::query!("DELETE FROM user_totp WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE personal_access_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,117 @@
+// CREATE TABLE IF NOT EXISTS login_throttles (
+//     scope VARCHAR(10) NOT NULL,
+//     key VARCHAR(100) NOT NULL,
+//     failures INT NOT NULL,
+//     last_failure_at TIMESTAMP NOT NULL,
+//     PRIMARY KEY (scope, key)
+// );
+
+use super::super::error::RepoError;
+
+/// What a throttle counter belongs to, the same key may be throttled in both
+#[derive(Debug, Clone, Copy)]
+pub enum ThrottleScope {
+    Account,
+    Ip,
+}
+
+impl ThrottleScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            ThrottleScope::Account => "account",
+            ThrottleScope::Ip => "ip",
+        }
+    }
+}
+
+/// Recent failed logins for an account or ip
+#[derive(Debug, Default)]
+pub struct FailedAttempts {
+    pub count: i64,
+    pub seconds_since_last: Option<f64>,
+}
+
+#[async_trait::async_trait]
+pub trait LoginThrottleRepo {
+    /// Counts an attempt as failed unless the key is locked, checking and counting in one statement
+    /// so parallel attempts can't all get in before the first is counted. The key is locked for
+    /// `lockout_seconds[n]` after `max_failures + n` failures, counting restarts after a quiet window.
+    /// Returns the failures including this attempt, None when it was refused
+    async fn login_throttle_attempt(&self, scope: ThrottleScope, key: &str, max_failures: i32, lockout_seconds: &[f64], window_minutes: i32) -> Result<Option<i32>, RepoError>;
+    async fn login_throttle_failures(&self, scope: ThrottleScope, key: &str, window_minutes: i32) -> Result<FailedAttempts, RepoError>;
+    /// Takes back an attempt that turned out not to be a failure
+    async fn login_throttle_refund(&self, scope: ThrottleScope, key: &str) -> Result<(), RepoError>;
+    async fn login_throttle_reset(&self, scope: ThrottleScope, key: &str) -> Result<(), RepoError>;
+}
+
+#[async_trait::async_trait]
+impl LoginThrottleRepo for super::super::Repository {
+    async fn login_throttle_attempt(&self, scope: ThrottleScope, key: &str, max_failures: i32, lockout_seconds: &[f64], window_minutes: i32) -> Result<Option<i32>, RepoError> {
+        let failures = sqlx::query_scalar!(
+            "
+            INSERT INTO login_throttles AS t (scope, key, failures, last_failure_at)
+            VALUES ($1, $2, 1, NOW())
+            ON CONFLICT (scope, key) DO UPDATE SET
+                failures = CASE WHEN t.last_failure_at <= NOW() - make_interval(mins => $3) THEN 1 ELSE t.failures + 1 END,
+                last_failure_at = NOW()
+            WHERE t.failures < $4
+                OR t.last_failure_at <= NOW() - make_interval(mins => $3)
+                OR t.last_failure_at + make_interval(secs => ($5::float8[])[LEAST(t.failures - $4 + 1, cardinality($5::float8[]))]) <= NOW()
+            RETURNING failures
+            ",
+            scope.as_str(),
+            key,
+            window_minutes,
+            max_failures,
+            lockout_seconds
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(failures)
+    }
+
+    async fn login_throttle_failures(&self, scope: ThrottleScope, key: &str, window_minutes: i32) -> Result<FailedAttempts, RepoError> {
+        let attempts = sqlx::query_as!(
+            FailedAttempts,
+            r#"
+            SELECT
+                failures::int8 AS "count!",
+                EXTRACT(EPOCH FROM NOW() - last_failure_at)::float8 AS seconds_since_last
+            FROM login_throttles
+            WHERE scope = $1 AND key = $2 AND last_failure_at > NOW() - make_interval(mins => $3)
+            "#,
+            scope.as_str(),
+            key,
+            window_minutes
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(attempts.unwrap_or_default())
+    }
+
+    async fn login_throttle_refund(&self, scope: ThrottleScope, key: &str) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE login_throttles SET failures = failures - 1 WHERE scope = $1 AND key = $2 AND failures > 0",
+            scope.as_str(),
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn login_throttle_reset(&self, scope: ThrottleScope, key: &str) -> Result<(), RepoError> {
+        sqlx::query!(
+            "DELETE FROM login_throttles WHERE scope = $1 AND key = $2",
+            scope.as_str(),
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+// CREATE TABLE IF NOT EXISTS magic_links (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP NOT NULL,
+//     used_at TIMESTAMP
+// );
+
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+#[async_trait::async_trait]
+pub trait MagicLinkRepo {
+    /// Records an issued link, `id` is the `jti` of its signed token
+    async fn magic_link_create(&self, id: Uuid, user_id: Uuid, expires_in_minutes: i32) -> Result<(), RepoError>;
+    /// Marks the link as used and returns its user, `None` if it was already used or expired
+    async fn magic_link_use(&self, id: Uuid) -> Result<Option<Uuid>, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl MagicLinkRepo for super::super::Repository {
+    async fn magic_link_create(&self, id: Uuid, user_id: Uuid, expires_in_minutes: i32) -> Result<(), RepoError> {
+        sqlx::query!(
+            "
+            INSERT INTO magic_links (id, user_id, expires_at)
+            VALUES ($1, $2, NOW() + make_interval(mins => $3))
+            ",
+            id,
+            user_id,
+            expires_in_minutes
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn magic_link_use(&self, id: Uuid) -> Result<Option<Uuid>, RepoError> {
+        // a single conditional update so two clicks racing each other cannot both succeed
+        let user_id = sqlx::query_scalar!(
+            "
+            UPDATE magic_links SET used_at = NOW()
+            WHERE id = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id
+            ",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_id)
+    }
+}
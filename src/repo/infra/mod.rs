@@ -1 +1,16 @@
+pub mod email_change;
+pub mod identity;
+pub mod login_throttle;
+pub mod password_reset;
+pub mod permission;
+pub mod personal_access_token;
+pub mod magic_link;
+pub mod organization;
+pub mod recovery_code;
+pub mod refresh_token;
+pub mod role;
+pub mod session;
+pub mod totp;
+pub mod two_factor_challenge;
 pub mod user;
+pub mod webauthn;
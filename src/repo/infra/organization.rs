@@ -0,0 +1,402 @@
+// CREATE TABLE IF NOT EXISTS organizations (
+//     id uuid PRIMARY KEY,
+//     name VARCHAR(100) NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+// );
+//
+// CREATE TABLE IF NOT EXISTS memberships (
+//     organization_id uuid NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     role VARCHAR(20) NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     PRIMARY KEY (organization_id, user_id)
+// );
+//
+// CREATE TABLE IF NOT EXISTS organization_invitations (
+//     id uuid PRIMARY KEY,
+//     organization_id uuid NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+//     email VARCHAR(100) NOT NULL,
+//     role VARCHAR(20) NOT NULL CHECK (role IN ('admin', 'member')),
+//     invited_by uuid REFERENCES users(id) ON DELETE SET NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP NOT NULL,
+//     accepted_at TIMESTAMP
+// );
+//
+// ALTER TABLE sessions ADD COLUMN active_organization_id uuid REFERENCES organizations(id) ON DELETE SET NULL;
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::repo::{tenant::TenantRepository, utils::is_valid_email};
+
+use super::super::error::RepoError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    Owner,
+    Admin,
+    Member,
+}
+
+impl OrgRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Admin => "admin",
+            Self::Member => "member",
+        }
+    }
+
+    fn parse(role: &str) -> Self {
+        match role {
+            "owner" => Self::Owner,
+            "admin" => Self::Admin,
+            // the column is checked, anything else can't be stored
+            _ => Self::Member,
+        }
+    }
+
+    /// Owners bring in admins and members, admins only members, nobody is invited as an owner
+    pub fn can_invite(&self, role: OrgRole) -> bool {
+        matches!((self, role), (Self::Owner, Self::Admin | Self::Member) | (Self::Admin, Self::Member))
+    }
+}
+
+#[derive(Debug)]
+pub struct Membership {
+    pub organization_id: Uuid,
+    pub organization_name: String,
+    pub role: OrgRole,
+}
+
+#[derive(Debug)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub role: OrgRole,
+}
+
+#[derive(Debug)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub organization_name: String,
+    pub email: String,
+    pub role: OrgRole,
+    pub inviter_name: Option<String>,
+}
+
+pub enum InvitationOutcome {
+    Joined { organization_id: Uuid },
+    /// The invitation was sent to another address than the signed in account's
+    WrongAccount,
+    /// The invitation does not exist, expired or was already accepted
+    Invalid,
+}
+
+pub const INVITATION_EXP_DAYS: i32 = 7;
+
+#[async_trait::async_trait]
+pub trait OrganizationRepo {
+    /// Creates the organization with `user_id` as its owner
+    async fn organization_create(&self, user_id: Uuid, name: &str) -> Result<Uuid, RepoError>;
+    async fn membership_list_for_user(&self, user_id: Uuid) -> Result<Vec<Membership>, RepoError>;
+    async fn session_set_organization(&self, session_id: Uuid, organization_id: Uuid) -> Result<(), RepoError>;
+    /// The organization the session last switched to while the user is still a member of it,
+    /// otherwise, or without a session like for personal access tokens, the user's oldest membership
+    async fn session_active_organization(&self, session_id: Option<Uuid>, user_id: Uuid) -> Result<Option<Uuid>, RepoError>;
+    /// A pending invitation that has not expired
+    async fn invitation_get(&self, id: Uuid) -> Result<Option<Invitation>, RepoError>;
+    async fn invitation_accept(&self, id: Uuid, user_id: Uuid, email: &str) -> Result<InvitationOutcome, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl OrganizationRepo for super::super::Repository {
+    async fn organization_create(&self, user_id: Uuid, name: &str) -> Result<Uuid, RepoError> {
+        let id = Uuid::new_v4();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO organizations (id, name) VALUES ($1, $2)",
+            id,
+            name
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO memberships (organization_id, user_id, role) VALUES ($1, $2, $3)",
+            id,
+            user_id,
+            OrgRole::Owner.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn membership_list_for_user(&self, user_id: Uuid) -> Result<Vec<Membership>, RepoError> {
+        let rows = sqlx::query!(
+            "
+            SELECT memberships.organization_id, organizations.name, memberships.role
+            FROM memberships
+            JOIN organizations ON organizations.id = memberships.organization_id
+            WHERE memberships.user_id = $1
+            ORDER BY memberships.created_at
+            ",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Membership {
+                organization_id: row.organization_id,
+                organization_name: row.name,
+                role: OrgRole::parse(&row.role),
+            })
+            .collect())
+    }
+
+    async fn session_set_organization(&self, session_id: Uuid, organization_id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE sessions SET active_organization_id = $1 WHERE id = $2",
+            organization_id,
+            session_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn session_active_organization(&self, session_id: Option<Uuid>, user_id: Uuid) -> Result<Option<Uuid>, RepoError> {
+        let organization_id = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(
+                (
+                    SELECT memberships.organization_id FROM sessions
+                    JOIN memberships ON memberships.organization_id = sessions.active_organization_id
+                        AND memberships.user_id = sessions.user_id
+                    WHERE sessions.id = $1 AND sessions.user_id = $2
+                ),
+                (
+                    SELECT organization_id FROM memberships
+                    WHERE user_id = $2
+                    ORDER BY created_at
+                    LIMIT 1
+                )
+            ) AS organization_id
+            "#,
+            session_id,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(organization_id)
+    }
+
+    async fn invitation_get(&self, id: Uuid) -> Result<Option<Invitation>, RepoError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                organization_invitations.id,
+                organization_invitations.organization_id,
+                organizations.name AS organization_name,
+                organization_invitations.email,
+                organization_invitations.role,
+                users.name AS "inviter_name?"
+            FROM organization_invitations
+            JOIN organizations ON organizations.id = organization_invitations.organization_id
+            LEFT JOIN users ON users.id = organization_invitations.invited_by
+            WHERE organization_invitations.id = $1
+                AND organization_invitations.accepted_at IS NULL
+                AND organization_invitations.expires_at > NOW()
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| Invitation {
+            id: row.id,
+            organization_id: row.organization_id,
+            organization_name: row.organization_name,
+            email: row.email,
+            role: OrgRole::parse(&row.role),
+            inviter_name: row.inviter_name,
+        }))
+    }
+
+    async fn invitation_accept(&self, id: Uuid, user_id: Uuid, email: &str) -> Result<InvitationOutcome, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(invitation) = sqlx::query!(
+            "
+            SELECT organization_id, email, role FROM organization_invitations
+            WHERE id = $1 AND accepted_at IS NULL AND expires_at > NOW()
+            FOR UPDATE
+            ",
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await? else {
+            return Ok(InvitationOutcome::Invalid);
+        };
+
+        if !invitation.email.eq_ignore_ascii_case(email) {
+            return Ok(InvitationOutcome::WrongAccount);
+        }
+
+        // someone who already is a member keeps their role
+        sqlx::query!(
+            "
+            INSERT INTO memberships (organization_id, user_id, role) VALUES ($1, $2, $3)
+            ON CONFLICT (organization_id, user_id) DO NOTHING
+            ",
+            invitation.organization_id,
+            user_id,
+            invitation.role
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE organization_invitations SET accepted_at = NOW() WHERE id = $1",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(InvitationOutcome::Joined { organization_id: invitation.organization_id })
+    }
+}
+
+/// Queries on the organization the `TenantRepository` is scoped to, none of them filter by
+/// organization themselves, row level security does
+#[async_trait::async_trait]
+pub trait TenantOrganizationRepo {
+    async fn member_role(&self, user_id: Uuid) -> Result<Option<OrgRole>, RepoError>;
+    async fn members(&self) -> Result<Vec<Member>, RepoError>;
+    /// Replaces any pending invitation of the same address
+    async fn invitation_create(&self, email: &str, role: OrgRole, invited_by: Uuid) -> Result<Uuid, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl TenantOrganizationRepo for TenantRepository {
+    async fn member_role(&self, user_id: Uuid) -> Result<Option<OrgRole>, RepoError> {
+        let mut tx = self.begin().await?;
+
+        let role = sqlx::query_scalar!(
+            "SELECT role FROM memberships WHERE user_id = $1",
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(role.as_deref().map(OrgRole::parse))
+    }
+
+    async fn members(&self) -> Result<Vec<Member>, RepoError> {
+        let mut tx = self.begin().await?;
+
+        let rows = sqlx::query!(
+            "
+            SELECT users.id, users.name, users.email, memberships.role
+            FROM memberships
+            JOIN users ON users.id = memberships.user_id
+            ORDER BY memberships.created_at
+            "
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Member {
+                user_id: row.id,
+                name: row.name,
+                email: row.email,
+                role: OrgRole::parse(&row.role),
+            })
+            .collect())
+    }
+
+    async fn invitation_create(&self, email: &str, role: OrgRole, invited_by: Uuid) -> Result<Uuid, RepoError> {
+        is_valid_email(email)?;
+
+        let id = Uuid::new_v4();
+        let mut tx = self.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM organization_invitations WHERE LOWER(email) = LOWER($1) AND accepted_at IS NULL",
+            email
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // the policy's check refuses any other organization id
+        sqlx::query!(
+            "
+            INSERT INTO organization_invitations (id, organization_id, email, role, invited_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(days => $6))
+            ",
+            id,
+            self.tenant_id(),
+            email,
+            role.as_str(),
+            invited_by,
+            INVITATION_EXP_DAYS
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners_invite_admins_and_members() {
+        assert!(OrgRole::Owner.can_invite(OrgRole::Admin));
+        assert!(OrgRole::Owner.can_invite(OrgRole::Member));
+    }
+
+    #[test]
+    fn admins_only_invite_members() {
+        assert!(OrgRole::Admin.can_invite(OrgRole::Member));
+        assert!(!OrgRole::Admin.can_invite(OrgRole::Admin));
+    }
+
+    #[test]
+    fn members_invite_nobody() {
+        assert!(!OrgRole::Member.can_invite(OrgRole::Member));
+        assert!(!OrgRole::Member.can_invite(OrgRole::Admin));
+    }
+
+    #[test]
+    fn nobody_is_invited_as_an_owner() {
+        for role in [OrgRole::Owner, OrgRole::Admin, OrgRole::Member] {
+            assert!(!role.can_invite(OrgRole::Owner), "{}", role.as_str());
+        }
+    }
+}
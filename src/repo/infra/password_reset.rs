@@ -0,0 +1,134 @@
+// CREATE TABLE IF NOT EXISTS password_resets (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP NOT NULL,
+//     failed_attempts INT DEFAULT 0 NOT NULL,
+//     authorized_at TIMESTAMP,
+//     used_at TIMESTAMP,
+//     code_hash VARCHAR(255) NOT NULL
+// );
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+pub enum PasswordResetOutcome {
+    /// The code matched, the reset may now change the password
+    Authorized,
+    WrongCode,
+    /// The reset ran out of attempts, expired or was already used
+    Expired,
+    /// There is no such reset, e.g. the decoy claims of privacy mode
+    NotFound,
+}
+
+#[async_trait::async_trait]
+pub trait PasswordResetRepo {
+    /// Records a reset, `id` is the `jti` of its claim. Only a hash of `code` is stored and
+    /// older unused resets of the user stop working
+    async fn password_reset_create(&self, id: Uuid, user_id: Uuid, code: &str, expires_in_minutes: i32) -> Result<(), RepoError>;
+    /// Checks the code against the reset, counting it when wrong, and marks the reset as
+    /// authorized when it matches
+    async fn password_reset_authorize(&self, id: Uuid, code: &str) -> Result<PasswordResetOutcome, RepoError>;
+}
+
+pub const PASSWORD_RESET_MAX_ATTEMPTS: i32 = 5;
+
+#[async_trait::async_trait]
+impl PasswordResetRepo for super::super::Repository {
+    async fn password_reset_create(&self, id: Uuid, user_id: Uuid, code: &str, expires_in_minutes: i32) -> Result<(), RepoError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let code_hash = Argon2::default().hash_password(code.as_bytes(), &salt)?.to_string();
+
+        let mut tx = self.pool.begin().await?;
+
+        // otherwise requesting many codes would multiply the guesses an attacker gets
+        sqlx::query!(
+            "DELETE FROM password_resets WHERE user_id = $1 AND used_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO password_resets (id, user_id, code_hash, expires_at)
+            VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))
+            ",
+            id,
+            user_id,
+            code_hash,
+            expires_in_minutes
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn password_reset_authorize(&self, id: Uuid, code: &str) -> Result<PasswordResetOutcome, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        // the row stays locked while the code is checked, so parallel guesses are counted one after another
+        let Some(reset) = sqlx::query!(
+            r#"
+            SELECT code_hash, used_at IS NULL AND expires_at > NOW() AND failed_attempts < $2 AS "usable!"
+            FROM password_resets
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            id,
+            PASSWORD_RESET_MAX_ATTEMPTS
+        )
+        .fetch_optional(&mut *tx)
+        .await? else {
+            return Ok(PasswordResetOutcome::NotFound);
+        };
+
+        if !reset.usable {
+            return Ok(PasswordResetOutcome::Expired);
+        }
+
+        let parsed_hash = PasswordHash::new(&reset.code_hash)?;
+        match Argon2::default().verify_password(code.as_bytes(), &parsed_hash) {
+            Ok(_) => {
+                sqlx::query!(
+                    "UPDATE password_resets SET authorized_at = NOW() WHERE id = $1",
+                    id
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+
+                Ok(PasswordResetOutcome::Authorized)
+            },
+            Err(argon2::password_hash::Error::Password) => {
+                let usable = sqlx::query_scalar!(
+                    r#"
+                    UPDATE password_resets SET failed_attempts = failed_attempts + 1
+                    WHERE id = $1
+                    RETURNING failed_attempts < $2 AS "usable!"
+                    "#,
+                    id,
+                    PASSWORD_RESET_MAX_ATTEMPTS
+                )
+                .fetch_one(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+
+                // after too many wrong codes the reset is dead and a new code has to be requested
+                Ok(if usable { PasswordResetOutcome::WrongCode } else { PasswordResetOutcome::Expired })
+            },
+            Err(e) => Err(RepoError::PasswordHashing(e)),
+        }
+    }
+}
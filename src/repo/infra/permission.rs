@@ -0,0 +1,111 @@
+// CREATE TABLE IF NOT EXISTS permissions (
+//     name VARCHAR(100) PRIMARY KEY,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+// );
+//
+// CREATE TABLE IF NOT EXISTS role_permissions (
+//     role VARCHAR(50) NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+//     permission VARCHAR(100) NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+//     PRIMARY KEY (role, permission)
+// );
+//
+// CREATE TABLE IF NOT EXISTS user_permissions (
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     permission VARCHAR(100) NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+//     granted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     PRIMARY KEY (user_id, permission)
+// );
+
+use uuid::Uuid;
+
+use crate::repo::utils::is_foreign_key_violation;
+
+use super::super::error::RepoError;
+
+#[async_trait::async_trait]
+pub trait PermissionRepo {
+    /// Everything the user was granted, directly or through one of their roles
+    async fn user_permissions(&self, user_id: Uuid) -> Result<Vec<String>, RepoError>;
+    /// Returns false if the permission does not exist
+    async fn permission_grant_to_user(&self, user_id: Uuid, permission: &str) -> Result<bool, RepoError>;
+    async fn permission_revoke_from_user(&self, user_id: Uuid, permission: &str) -> Result<(), RepoError>;
+    /// Returns false if the role or the permission does not exist
+    async fn permission_grant_to_role(&self, role: &str, permission: &str) -> Result<bool, RepoError>;
+    async fn permission_revoke_from_role(&self, role: &str, permission: &str) -> Result<(), RepoError>;
+}
+
+#[async_trait::async_trait]
+impl PermissionRepo for super::super::Repository {
+    async fn user_permissions(&self, user_id: Uuid) -> Result<Vec<String>, RepoError> {
+        let permissions = sqlx::query_scalar!(
+            r#"
+            SELECT permission AS "permission!" FROM user_permissions WHERE user_id = $1
+            UNION
+            SELECT role_permissions.permission FROM role_permissions
+            JOIN user_roles ON user_roles.role = role_permissions.role
+            WHERE user_roles.user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(permissions)
+    }
+
+    async fn permission_grant_to_user(&self, user_id: Uuid, permission: &str) -> Result<bool, RepoError> {
+        let inserted = sqlx::query!(
+            "INSERT INTO user_permissions (user_id, permission) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            user_id,
+            permission
+        )
+        .execute(&self.pool)
+        .await;
+
+        match inserted {
+            Ok(_) => Ok(true),
+            Err(err) if is_foreign_key_violation(&err) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn permission_revoke_from_user(&self, user_id: Uuid, permission: &str) -> Result<(), RepoError> {
+        sqlx::query!(
+            "DELETE FROM user_permissions WHERE user_id = $1 AND permission = $2",
+            user_id,
+            permission
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn permission_grant_to_role(&self, role: &str, permission: &str) -> Result<bool, RepoError> {
+        let inserted = sqlx::query!(
+            "INSERT INTO role_permissions (role, permission) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            role,
+            permission
+        )
+        .execute(&self.pool)
+        .await;
+
+        match inserted {
+            Ok(_) => Ok(true),
+            Err(err) if is_foreign_key_violation(&err) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn permission_revoke_from_role(&self, role: &str, permission: &str) -> Result<(), RepoError> {
+        sqlx::query!(
+            "DELETE FROM role_permissions WHERE role = $1 AND permission = $2",
+            role,
+            permission
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+// CREATE TABLE IF NOT EXISTS personal_access_tokens (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     name VARCHAR(100) NOT NULL,
+//     token_hash VARCHAR(64) UNIQUE NOT NULL,
+//     scopes TEXT[] NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP,
+//     last_used_at TIMESTAMP,
+//     revoked_at TIMESTAMP
+// );
+
+use uuid::Uuid;
+
+use crate::repo::utils::{generate_token, hash_token};
+
+use super::super::error::RepoError;
+
+#[derive(Debug)]
+pub struct PersonalAccessToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: sqlx::types::chrono::NaiveDateTime,
+    // None never expires
+    pub expires_at: Option<sqlx::types::chrono::NaiveDateTime>,
+    pub last_used_at: Option<sqlx::types::chrono::NaiveDateTime>,
+}
+
+impl PersonalAccessToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at < chrono::Utc::now().naive_utc())
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PersonalAccessTokenRepo {
+    /// Returns the token in plain text, only its hash is stored so it can't be shown again
+    async fn personal_access_token_create(&self, user_id: Uuid, name: &str, scopes: &[String], expires_in_days: Option<i32>) -> Result<String, RepoError>;
+    /// Tokens that were not revoked, expired ones included
+    async fn personal_access_token_list_for_user(&self, user_id: Uuid) -> Result<Vec<PersonalAccessToken>, RepoError>;
+    async fn personal_access_token_revoke(&self, id: Uuid, user_id: Uuid) -> Result<(), RepoError>;
+    async fn personal_access_token_revoke_all_for_user(&self, user_id: Uuid) -> Result<u64, RepoError>;
+    /// Returns the token if it is valid, bumping its `last_used_at`
+    async fn personal_access_token_touch(&self, token: &str) -> Result<Option<PersonalAccessToken>, RepoError>;
+}
+
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "ahp_pat_";
+pub const PERSONAL_ACCESS_TOKEN_LENGTH: usize = 40;
+
+#[async_trait::async_trait]
+impl PersonalAccessTokenRepo for super::super::Repository {
+    async fn personal_access_token_create(&self, user_id: Uuid, name: &str, scopes: &[String], expires_in_days: Option<i32>) -> Result<String, RepoError> {
+        // the prefix makes leaked tokens easy to recognize, e.g. by secret scanners
+        let token = format!("{}{}", PERSONAL_ACCESS_TOKEN_PREFIX, generate_token(PERSONAL_ACCESS_TOKEN_LENGTH));
+
+        sqlx::query!(
+            "
+            INSERT INTO personal_access_tokens (id, user_id, name, token_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(days => $6))
+            ",
+            Uuid::new_v4(),
+            user_id,
+            name,
+            hash_token(&token),
+            scopes,
+            expires_in_days
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn personal_access_token_list_for_user(&self, user_id: Uuid) -> Result<Vec<PersonalAccessToken>, RepoError> {
+        let tokens = sqlx::query_as!(
+            PersonalAccessToken,
+            "
+            SELECT id, user_id, name, scopes, created_at, expires_at, last_used_at
+            FROM personal_access_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY created_at DESC
+            ",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    async fn personal_access_token_revoke(&self, id: Uuid, user_id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE personal_access_tokens SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+            id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn personal_access_token_revoke_all_for_user(&self, user_id: Uuid) -> Result<u64, RepoError> {
+        let res = sqlx::query!(
+            "UPDATE personal_access_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    async fn personal_access_token_touch(&self, token: &str) -> Result<Option<PersonalAccessToken>, RepoError> {
+        let token = sqlx::query_as!(
+            PersonalAccessToken,
+            "
+            UPDATE personal_access_tokens
+            SET last_used_at = NOW()
+            WHERE token_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())
+            RETURNING id, user_id, name, scopes, created_at, expires_at, last_used_at
+            ",
+            hash_token(token)
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+}
@@ -0,0 +1,113 @@
+// CREATE TABLE IF NOT EXISTS recovery_codes (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     code_hash VARCHAR(255) NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     used_at TIMESTAMP
+// );
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeRepo {
+    /// Replaces all of the user's recovery codes, the plain codes are never stored
+    async fn recovery_codes_replace(&self, user_id: Uuid, codes: &[String]) -> Result<(), RepoError>;
+    /// Marks a matching unused code as used, returns false if none matched
+    async fn recovery_code_use(&self, user_id: Uuid, code: &str) -> Result<bool, RepoError>;
+    async fn recovery_codes_remaining(&self, user_id: Uuid) -> Result<i64, RepoError>;
+    async fn recovery_codes_delete(&self, user_id: Uuid) -> Result<(), RepoError>;
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeRepo for super::super::Repository {
+    async fn recovery_codes_replace(&self, user_id: Uuid, codes: &[String]) -> Result<(), RepoError> {
+        let argon2 = Argon2::default();
+        let mut hashes = Vec::with_capacity(codes.len());
+        for code in codes {
+            let salt = SaltString::generate(&mut OsRng);
+            hashes.push(argon2.hash_password(code.as_bytes(), &salt)?.to_string());
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM recovery_codes WHERE user_id = $1",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for hash in hashes {
+            sqlx::query!(
+                "INSERT INTO recovery_codes (id, user_id, code_hash) VALUES ($1, $2, $3)",
+                Uuid::new_v4(),
+                user_id,
+                hash
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn recovery_code_use(&self, user_id: Uuid, code: &str) -> Result<bool, RepoError> {
+        let unused = sqlx::query!(
+            "SELECT id, code_hash FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for row in unused {
+            let parsed_hash = PasswordHash::new(&row.code_hash)?;
+            match Argon2::default().verify_password(code.as_bytes(), &parsed_hash) {
+                Ok(_) => {
+                    // the used_at check makes sure two concurrent logins cant both spend the code
+                    let res = sqlx::query!(
+                        "UPDATE recovery_codes SET used_at = NOW() WHERE id = $1 AND used_at IS NULL",
+                        row.id
+                    )
+                    .execute(&self.pool)
+                    .await?;
+
+                    return Ok(res.rows_affected() == 1);
+                },
+                Err(argon2::password_hash::Error::Password) => continue,
+                Err(e) => return Err(RepoError::PasswordHashing(e)),
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn recovery_codes_remaining(&self, user_id: Uuid) -> Result<i64, RepoError> {
+        let remaining = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL"#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(remaining)
+    }
+
+    async fn recovery_codes_delete(&self, user_id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "DELETE FROM recovery_codes WHERE user_id = $1",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+// CREATE TABLE IF NOT EXISTS refresh_tokens (
+//     id uuid PRIMARY KEY,
+//     session_id uuid NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+//     token_hash VARCHAR(64) UNIQUE NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP NOT NULL,
+//     used_at TIMESTAMP
+// );
+
+use uuid::Uuid;
+
+use crate::repo::utils::{generate_token, hash_token};
+
+use super::super::error::RepoError;
+
+pub enum RefreshOutcome {
+    /// The token was valid and has been exchanged for `token`
+    Rotated { user_id: Uuid, session_id: Uuid, token: String },
+    /// The token was rotated moments ago by a concurrent request, a new access token
+    /// may be issued but the replacement refresh token is not handed out twice
+    Concurrent { user_id: Uuid, session_id: Uuid },
+    /// An already rotated token was presented again, the whole session has been revoked
+    Reused,
+    Invalid,
+}
+
+#[async_trait::async_trait]
+pub trait RefreshTokenRepo {
+    /// Issues a new refresh token for the session and returns it in plain text, only the hash is stored
+    async fn refresh_token_create(&self, session_id: Uuid) -> Result<String, RepoError>;
+    /// Consumes a refresh token and issues its replacement in the same session
+    async fn refresh_token_rotate(&self, token: &str) -> Result<RefreshOutcome, RepoError>;
+}
+
+pub const REFRESH_TOKEN_LENGTH: usize = 48;
+pub const REFRESH_TOKEN_EXP_DAYS: i32 = 30;
+// parallel requests (htmx loads several fragments at once) all present the same token
+pub const REFRESH_TOKEN_GRACE_SECONDS: f64 = 30.0;
+
+#[async_trait::async_trait]
+impl RefreshTokenRepo for super::super::Repository {
+    async fn refresh_token_create(&self, session_id: Uuid) -> Result<String, RepoError> {
+        let token = generate_token(REFRESH_TOKEN_LENGTH);
+
+        sqlx::query!(
+            "
+            INSERT INTO refresh_tokens (id, session_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, NOW() + make_interval(days => $4))
+            ",
+            Uuid::new_v4(),
+            session_id,
+            hash_token(&token),
+            REFRESH_TOKEN_EXP_DAYS
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn refresh_token_rotate(&self, token: &str) -> Result<RefreshOutcome, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                rt.id,
+                rt.session_id,
+                s.user_id,
+                rt.used_at IS NOT NULL AS "used!",
+                COALESCE(rt.used_at > NOW() - make_interval(secs => $2), false) AS "in_grace!",
+                rt.expires_at < NOW() OR s.revoked_at IS NOT NULL AS "expired!"
+            FROM refresh_tokens rt
+            JOIN sessions s ON s.id = rt.session_id
+            WHERE rt.token_hash = $1
+            FOR UPDATE OF rt
+            "#,
+            hash_token(token),
+            REFRESH_TOKEN_GRACE_SECONDS
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(RefreshOutcome::Invalid);
+        };
+
+        if row.expired {
+            return Ok(RefreshOutcome::Invalid);
+        }
+
+        if row.used && row.in_grace {
+            return Ok(RefreshOutcome::Concurrent { user_id: row.user_id, session_id: row.session_id });
+        }
+
+        if row.used {
+            tracing::warn!("Refresh token reuse detected, revoking session {}", row.session_id);
+            sqlx::query!(
+                "UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+                row.session_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            return Ok(RefreshOutcome::Reused);
+        }
+
+        sqlx::query!(
+            "UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1",
+            row.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let new_token = generate_token(REFRESH_TOKEN_LENGTH);
+        sqlx::query!(
+            "
+            INSERT INTO refresh_tokens (id, session_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, NOW() + make_interval(days => $4))
+            ",
+            Uuid::new_v4(),
+            row.session_id,
+            hash_token(&new_token),
+            REFRESH_TOKEN_EXP_DAYS
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(RefreshOutcome::Rotated { user_id: row.user_id, session_id: row.session_id, token: new_token })
+    }
+}
@@ -0,0 +1,152 @@
+// CREATE TABLE IF NOT EXISTS roles (
+//     name VARCHAR(50) PRIMARY KEY,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+// );
+//
+// CREATE TABLE IF NOT EXISTS user_roles (
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     role VARCHAR(50) NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+//     granted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     PRIMARY KEY (user_id, role)
+// );
+//
+// ALTER TABLE users ADD COLUMN role_version INTEGER NOT NULL DEFAULT 0;
+
+use uuid::Uuid;
+
+use crate::repo::utils::is_foreign_key_violation;
+
+use super::super::error::RepoError;
+
+/// A user's roles together with the `users.role_version` they were read at
+#[derive(Debug, Clone, Default)]
+pub struct UserRoles {
+    pub version: i32,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct UserWithRoles {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub roles: Vec<String>,
+}
+
+#[async_trait::async_trait]
+pub trait RoleRepo {
+    async fn role_list(&self) -> Result<Vec<String>, RepoError>;
+    async fn user_roles(&self, user_id: Uuid) -> Result<UserRoles, RepoError>;
+    /// Returns false if the role does not exist, granting a role the user already has is a no-op
+    async fn role_grant(&self, user_id: Uuid, role: &str) -> Result<bool, RepoError>;
+    async fn role_revoke(&self, user_id: Uuid, role: &str) -> Result<(), RepoError>;
+    async fn user_list_with_roles(&self) -> Result<Vec<UserWithRoles>, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl RoleRepo for super::super::Repository {
+    async fn role_list(&self) -> Result<Vec<String>, RepoError> {
+        let roles = sqlx::query_scalar!("SELECT name FROM roles ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(roles)
+    }
+
+    async fn user_roles(&self, user_id: Uuid) -> Result<UserRoles, RepoError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                users.role_version,
+                COALESCE(array_agg(user_roles.role ORDER BY user_roles.role) FILTER (WHERE user_roles.role IS NOT NULL), '{}') AS "roles!"
+            FROM users
+            LEFT JOIN user_roles ON user_roles.user_id = users.id
+            WHERE users.id = $1
+            GROUP BY users.id
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row
+            .map(|row| UserRoles { version: row.role_version, roles: row.roles })
+            .unwrap_or_default())
+    }
+
+    async fn role_grant(&self, user_id: Uuid, role: &str) -> Result<bool, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let inserted = sqlx::query!(
+            "INSERT INTO user_roles (user_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            user_id,
+            role
+        )
+        .execute(&mut *tx)
+        .await;
+
+        match inserted {
+            Ok(result) if result.rows_affected() == 0 => return Ok(true),
+            Ok(_) => {},
+            Err(err) if is_foreign_key_violation(&err) => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+
+        sqlx::query!(
+            "UPDATE users SET role_version = role_version + 1 WHERE id = $1",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn role_revoke(&self, user_id: Uuid, role: &str) -> Result<(), RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted = sqlx::query!(
+            "DELETE FROM user_roles WHERE user_id = $1 AND role = $2",
+            user_id,
+            role
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if deleted.rows_affected() > 0 {
+            sqlx::query!(
+                "UPDATE users SET role_version = role_version + 1 WHERE id = $1",
+                user_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn user_list_with_roles(&self) -> Result<Vec<UserWithRoles>, RepoError> {
+        let users = sqlx::query_as!(
+            UserWithRoles,
+            r#"
+            SELECT
+                users.id,
+                users.name,
+                users.email,
+                COALESCE(array_agg(user_roles.role ORDER BY user_roles.role) FILTER (WHERE user_roles.role IS NOT NULL), '{}') AS "roles!"
+            FROM users
+            LEFT JOIN user_roles ON user_roles.user_id = users.id
+            GROUP BY users.id
+            ORDER BY users.created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(users)
+    }
+}
@@ -0,0 +1,138 @@
+// CREATE TABLE IF NOT EXISTS sessions (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     last_seen TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     user_agent TEXT,
+//     ip VARCHAR(45),
+//     revoked_at TIMESTAMP
+// );
+
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+#[derive(Debug)]
+pub struct Session {
+    pub id: sqlx::types::Uuid,
+    pub user_id: sqlx::types::Uuid,
+    pub created_at: sqlx::types::chrono::NaiveDateTime,
+    pub last_seen: sqlx::types::chrono::NaiveDateTime,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub revoked_at: Option<sqlx::types::chrono::NaiveDateTime>,
+}
+
+#[async_trait::async_trait]
+pub trait SessionRepo {
+    async fn session_create(&self, user_id: Uuid, user_agent: Option<&str>, ip: Option<&str>) -> Result<Uuid, RepoError>;
+    /// Returns the session if it exists and has not been revoked, bumping its `last_seen`
+    async fn session_touch(&self, id: Uuid) -> Result<Option<Session>, RepoError>;
+    async fn session_list_active_for_user(&self, user_id: Uuid) -> Result<Vec<Session>, RepoError>;
+    async fn session_revoke(&self, id: Uuid) -> Result<(), RepoError>;
+    /// Like `session_revoke` but only if the session belongs to the given user
+    async fn session_revoke_for_user(&self, id: Uuid, user_id: Uuid) -> Result<(), RepoError>;
+    /// Revokes all of a user's sessions except the one they are currently using
+    async fn session_revoke_others(&self, user_id: Uuid, keep_id: Uuid) -> Result<u64, RepoError>;
+    /// Revokes every active session of a user, used on password change or by an admin
+    async fn session_revoke_all_for_user(&self, user_id: Uuid) -> Result<u64, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl SessionRepo for super::super::Repository {
+    async fn session_create(&self, user_id: Uuid, user_agent: Option<&str>, ip: Option<&str>) -> Result<Uuid, RepoError> {
+        let id = sqlx::types::uuid::Uuid::new_v4();
+
+        sqlx::query!(
+            "INSERT INTO sessions (id, user_id, user_agent, ip) VALUES ($1, $2, $3, $4)",
+            id,
+            user_id,
+            user_agent,
+            ip
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    async fn session_touch(&self, id: Uuid) -> Result<Option<Session>, RepoError> {
+        let session = sqlx::query_as!(
+            Session,
+            "
+            UPDATE sessions
+            SET last_seen = NOW()
+            WHERE id = $1 AND revoked_at IS NULL
+            RETURNING id, user_id, created_at, last_seen, user_agent, ip, revoked_at
+            ",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn session_list_active_for_user(&self, user_id: Uuid) -> Result<Vec<Session>, RepoError> {
+        let sessions = sqlx::query_as!(
+            Session,
+            "
+            SELECT id, user_id, created_at, last_seen, user_agent, ip, revoked_at
+            FROM sessions
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY last_seen DESC
+            ",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    async fn session_revoke(&self, id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn session_revoke_for_user(&self, id: Uuid, user_id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+            id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn session_revoke_others(&self, user_id: Uuid, keep_id: Uuid) -> Result<u64, RepoError> {
+        let res = sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND id <> $2 AND revoked_at IS NULL",
+            user_id,
+            keep_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    async fn session_revoke_all_for_user(&self, user_id: Uuid) -> Result<u64, RepoError> {
+        let res = sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+}
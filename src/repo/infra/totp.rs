@@ -0,0 +1,107 @@
+// CREATE TABLE IF NOT EXISTS user_totp (
+//     user_id uuid PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+//     secret VARCHAR(64) NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     enabled_at TIMESTAMP,
+//     last_used_step BIGINT
+// );
+
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+#[derive(Debug)]
+pub struct UserTotp {
+    pub user_id: sqlx::types::Uuid,
+    pub secret: String,
+    pub created_at: sqlx::types::chrono::NaiveDateTime,
+    pub enabled_at: Option<sqlx::types::chrono::NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+}
+
+impl UserTotp {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+}
+
+#[async_trait::async_trait]
+pub trait TotpRepo {
+    async fn totp_get(&self, user_id: Uuid) -> Result<Option<UserTotp>, RepoError>;
+    /// Stores a secret awaiting confirmation, an already enabled secret is never replaced
+    async fn totp_set_pending(&self, user_id: Uuid, secret: &str) -> Result<(), RepoError>;
+    async fn totp_enable(&self, user_id: Uuid) -> Result<(), RepoError>;
+    async fn totp_delete(&self, user_id: Uuid) -> Result<(), RepoError>;
+    /// Records a time step as used, returns false if it (or a later one) was already used
+    async fn totp_use_step(&self, user_id: Uuid, step: i64) -> Result<bool, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl TotpRepo for super::super::Repository {
+    async fn totp_get(&self, user_id: Uuid) -> Result<Option<UserTotp>, RepoError> {
+        let totp = sqlx::query_as!(
+            UserTotp,
+            "SELECT user_id, secret, created_at, enabled_at, last_used_step FROM user_totp WHERE user_id = $1",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(totp)
+    }
+
+    async fn totp_set_pending(&self, user_id: Uuid, secret: &str) -> Result<(), RepoError> {
+        sqlx::query!(
+            "
+            INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, created_at = NOW(), last_used_step = NULL
+            WHERE user_totp.enabled_at IS NULL
+            ",
+            user_id,
+            secret
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn totp_enable(&self, user_id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE user_totp SET enabled_at = NOW() WHERE user_id = $1 AND enabled_at IS NULL",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn totp_delete(&self, user_id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "DELETE FROM user_totp WHERE user_id = $1",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn totp_use_step(&self, user_id: Uuid, step: i64) -> Result<bool, RepoError> {
+        let res = sqlx::query!(
+            "
+            UPDATE user_totp
+            SET last_used_step = $2
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            ",
+            user_id,
+            step
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,84 @@
+// CREATE TABLE IF NOT EXISTS two_factor_challenges (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP NOT NULL,
+//     attempts INT DEFAULT 0 NOT NULL,
+//     used_at TIMESTAMP
+// );
+
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+#[async_trait::async_trait]
+pub trait TwoFactorChallengeRepo {
+    /// Records a challenge, `id` is the `jti` of its claim. Older unused challenges of the user stop working
+    async fn two_factor_challenge_create(&self, id: Uuid, user_id: Uuid, expires_in_minutes: i32) -> Result<(), RepoError>;
+    /// Uses up one of the challenge's attempts before a code is checked, so concurrent guesses
+    /// can't get past the limit. Returns false once it is used, expired or out of attempts
+    async fn two_factor_challenge_attempt(&self, id: Uuid, user_id: Uuid) -> Result<bool, RepoError>;
+    /// Marks the challenge as answered, returns false if it already was
+    async fn two_factor_challenge_complete(&self, id: Uuid) -> Result<bool, RepoError>;
+}
+
+pub const TWO_FACTOR_MAX_ATTEMPTS: i32 = 5;
+
+#[async_trait::async_trait]
+impl TwoFactorChallengeRepo for super::super::Repository {
+    async fn two_factor_challenge_create(&self, id: Uuid, user_id: Uuid, expires_in_minutes: i32) -> Result<(), RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        // otherwise signing in again would hand out fresh attempts while the old ones still work
+        sqlx::query!(
+            "DELETE FROM two_factor_challenges WHERE user_id = $1 AND (used_at IS NULL OR expires_at < NOW())",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO two_factor_challenges (id, user_id, expires_at)
+            VALUES ($1, $2, NOW() + make_interval(mins => $3))
+            ",
+            id,
+            user_id,
+            expires_in_minutes
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn two_factor_challenge_attempt(&self, id: Uuid, user_id: Uuid) -> Result<bool, RepoError> {
+        let attempted = sqlx::query_scalar!(
+            "
+            UPDATE two_factor_challenges SET attempts = attempts + 1
+            WHERE id = $1 AND user_id = $2 AND used_at IS NULL AND expires_at > NOW() AND attempts < $3
+            RETURNING id
+            ",
+            id,
+            user_id,
+            TWO_FACTOR_MAX_ATTEMPTS
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(attempted.is_some())
+    }
+
+    async fn two_factor_challenge_complete(&self, id: Uuid) -> Result<bool, RepoError> {
+        let res = sqlx::query!(
+            "UPDATE two_factor_challenges SET used_at = NOW() WHERE id = $1 AND used_at IS NULL",
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected() == 1)
+    }
+}
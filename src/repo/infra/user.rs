@@ -3,7 +3,8 @@
 //     name VARCHAR(100) NOT NULL,
 //     email VARCHAR(100) UNIQUE NOT NULL,
 //     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
-//     password_hash VARCHAR(255) NOT NULL
+//     password_hash VARCHAR(255),
+//     email_verified_at TIMESTAMP
 // );
 
 use argon2::{
@@ -12,25 +13,37 @@ use argon2::{
 };
 use uuid::Uuid;
 
-use crate::{features::auth::claims::password_reset::PasswordResetClaim, repo::utils::{is_valid_email, is_valid_password}};
+use crate::{features::auth::claims::password_reset::PasswordResetClaim, repo::{tenant::TenantRepository, utils::{is_valid_email, is_valid_password}}};
 
 use super::super::error::RepoError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct User {
     pub id: sqlx::types::Uuid,
     pub name: String,
     pub email: String,
     pub created_at: sqlx::types::chrono::NaiveDateTime,
-    pub password_hash: String,
+    // None for accounts that sign in through an identity provider only
+    pub password_hash: Option<String>,
+    pub email_verified_at: Option<sqlx::types::chrono::NaiveDateTime>,
+}
+
+impl User {
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
 }
 
 #[async_trait::async_trait]
 pub trait UserRepo {
     async fn user_get_by_email(&self, email: &str) -> Result<Option<User>, RepoError>;
     async fn user_get_by_id(&self, id: sqlx::types::Uuid) -> Result<Option<User>, RepoError>;
-    async fn user_change_password(&self, id: sqlx::types::Uuid, new_password: String, _password_reset_claims_to_verify: PasswordResetClaim) -> Result<(), RepoError>;
+    /// Consumes the reset the claim belongs to, so each reset changes the password at most once
+    async fn user_change_password(&self, id: sqlx::types::Uuid, new_password: String, password_reset_claim: PasswordResetClaim) -> Result<(), RepoError>;
     async fn user_create(&self,email: &str, password: &str, name: &str) -> Result<Uuid, RepoError>;
+    /// Only for emails a trusted party already verified, e.g. an identity provider
+    async fn user_create_passwordless(&self, email: &str, name: &str) -> Result<Uuid, RepoError>;
+    async fn user_mark_email_verified(&self, id: Uuid) -> Result<(), RepoError>;
     async fn user_check_password(&self, email: &str, password: &str) -> Result<Option<Uuid>, RepoError>;
 }
 
@@ -39,7 +52,7 @@ impl UserRepo for super::super::Repository {
     async fn user_get_by_email(&self, email: &str) -> Result<Option<User>, RepoError> {
         let user = sqlx::query_as!(
             User,
-            "SELECT id, name, email, created_at, password_hash FROM users WHERE email = $1",
+            "SELECT id, name, email, created_at, password_hash, email_verified_at FROM users WHERE email = $1",
             email
         )
         .fetch_optional(&self.pool)
@@ -50,7 +63,7 @@ impl UserRepo for super::super::Repository {
     async fn user_get_by_id(&self, id: sqlx::types::Uuid) -> Result<Option<User>, RepoError> {
         let user = sqlx::query_as!(
             User,
-            "SELECT id, name, email, created_at, password_hash FROM users WHERE id = $1",
+            "SELECT id, name, email, created_at, password_hash, email_verified_at FROM users WHERE id = $1",
             id
         )
         .fetch_optional(&self.pool)
@@ -59,7 +72,7 @@ impl UserRepo for super::super::Repository {
         Ok(user)
     }
 
-    async fn user_change_password(&self, id: sqlx::types::Uuid, new_password: String, _password_reset_claims_to_verify: PasswordResetClaim) -> Result<(), RepoError> {
+    async fn user_change_password(&self, id: sqlx::types::Uuid, new_password: String, password_reset_claim: PasswordResetClaim) -> Result<(), RepoError> {
         is_valid_password(&new_password)?;
 
         let argon2 = argon2::Argon2::default();
@@ -67,6 +80,24 @@ impl UserRepo for super::super::Repository {
         let salt = SaltString::generate(&mut OsRng);
         let password_hash = argon2.hash_password(password, &salt)?.to_string();
 
+        let mut tx = self.pool.begin().await?;
+
+        let consumed = sqlx::query_scalar!(
+            "
+            UPDATE password_resets SET used_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND used_at IS NULL AND authorized_at IS NOT NULL AND expires_at > NOW()
+            RETURNING id
+            ",
+            password_reset_claim.jti,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if consumed.is_none() {
+            return Err(RepoError::PasswordResetInvalid);
+        }
+
         sqlx::query!(
             "
             UPDATE users
@@ -76,9 +107,11 @@ impl UserRepo for super::super::Repository {
             password_hash,
             id
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(())
     }
 
@@ -107,8 +140,37 @@ impl UserRepo for super::super::Repository {
         .execute(&self.pool)
         .await?;
 
-        return Ok(id);
+        Ok(id)
+    }
+
+    async fn user_create_passwordless(&self, email: &str, name: &str) -> Result<Uuid, RepoError> {
+        is_valid_email(email)?;
+
+        let id = sqlx::types::uuid::Uuid::new_v4();
+
+        sqlx::query!(
+            "INSERT INTO users (id, name, email, email_verified_at) VALUES ($1, $2, $3, NOW())",
+            id,
+            name,
+            email
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(id)
     }
+
+    async fn user_mark_email_verified(&self, id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE users SET email_verified_at = NOW() WHERE id = $1 AND email_verified_at IS NULL",
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     async fn user_check_password(&self, email: &str, password: &str) -> Result<Option<Uuid>, RepoError> {
         let user = match self.user_get_by_email(email).await {
             Ok(Some(user)) => user,
@@ -117,7 +179,9 @@ impl UserRepo for super::super::Repository {
         };
         
         
-        let correct_password = user.password_hash;
+        let Some(correct_password) = user.password_hash else {
+            return Ok(None);
+        };
         let password = password.as_bytes();
         let parsed_hash = PasswordHash::new(&correct_password)?;
         let res = Argon2::default().verify_password(password, &parsed_hash);
@@ -134,3 +198,52 @@ impl UserRepo for super::super::Repository {
     }
 }
 
+/// The columns of a user a tenant may read, the rest of the row isn't granted to `app_tenant`
+#[derive(Debug, Clone)]
+pub struct TenantUser {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+}
+
+/// Users as the organization the `TenantRepository` is scoped to sees them, which is only its members
+#[async_trait::async_trait]
+pub trait TenantUserRepo {
+    async fn user_get_by_id(&self, id: Uuid) -> Result<Option<TenantUser>, RepoError>;
+    async fn user_get_by_email(&self, email: &str) -> Result<Option<TenantUser>, RepoError>;
+}
+
+#[async_trait::async_trait]
+impl TenantUserRepo for TenantRepository {
+    async fn user_get_by_id(&self, id: Uuid) -> Result<Option<TenantUser>, RepoError> {
+        let mut tx = self.begin().await?;
+
+        let user = sqlx::query_as!(
+            TenantUser,
+            "SELECT id, name, email FROM users WHERE id = $1",
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn user_get_by_email(&self, email: &str) -> Result<Option<TenantUser>, RepoError> {
+        let mut tx = self.begin().await?;
+
+        let user = sqlx::query_as!(
+            TenantUser,
+            "SELECT id, name, email FROM users WHERE LOWER(email) = LOWER($1)",
+            email
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+}
@@ -0,0 +1,183 @@
+// CREATE TABLE IF NOT EXISTS webauthn_credentials (
+//     id uuid PRIMARY KEY,
+//     user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+//     credential_id BYTEA UNIQUE NOT NULL,
+//     passkey JSONB NOT NULL,
+//     name VARCHAR(100) NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     last_used_at TIMESTAMP
+// );
+//
+// CREATE TABLE IF NOT EXISTS webauthn_ceremonies (
+//     id uuid PRIMARY KEY,
+//     user_id uuid REFERENCES users(id) ON DELETE CASCADE,
+//     state JSONB NOT NULL,
+//     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+//     expires_at TIMESTAMP NOT NULL
+// );
+
+use uuid::Uuid;
+
+use super::super::error::RepoError;
+
+#[derive(Debug)]
+pub struct WebauthnCredential {
+    pub id: sqlx::types::Uuid,
+    pub user_id: sqlx::types::Uuid,
+    pub credential_id: Vec<u8>,
+    pub passkey: serde_json::Value,
+    pub name: String,
+    pub created_at: sqlx::types::chrono::NaiveDateTime,
+    pub last_used_at: Option<sqlx::types::chrono::NaiveDateTime>,
+}
+
+#[derive(Debug)]
+pub struct WebauthnCeremony {
+    pub user_id: Option<sqlx::types::Uuid>,
+    pub state: serde_json::Value,
+}
+
+pub const WEBAUTHN_CEREMONY_EXP_MINUTES: i32 = 5;
+
+#[async_trait::async_trait]
+pub trait WebauthnRepo {
+    /// Stores the state between a start and finish request, expired ceremonies and the user's
+    /// unfinished registration are removed on the way
+    async fn webauthn_ceremony_create(&self, user_id: Option<Uuid>, state: serde_json::Value) -> Result<Uuid, RepoError>;
+    /// Removes and returns the ceremony state, a ceremony can only be finished once
+    async fn webauthn_ceremony_take(&self, id: Uuid) -> Result<Option<WebauthnCeremony>, RepoError>;
+    async fn webauthn_credential_create(&self, user_id: Uuid, credential_id: &[u8], passkey: serde_json::Value, name: &str) -> Result<Uuid, RepoError>;
+    async fn webauthn_credential_get(&self, credential_id: &[u8]) -> Result<Option<WebauthnCredential>, RepoError>;
+    async fn webauthn_credentials_for_user(&self, user_id: Uuid) -> Result<Vec<WebauthnCredential>, RepoError>;
+    /// Stores the updated passkey (signature counter etc) after a successful login
+    async fn webauthn_credential_mark_used(&self, id: Uuid, passkey: serde_json::Value) -> Result<(), RepoError>;
+    async fn webauthn_credential_delete(&self, id: Uuid, user_id: Uuid) -> Result<(), RepoError>;
+}
+
+#[async_trait::async_trait]
+impl WebauthnRepo for super::super::Repository {
+    async fn webauthn_ceremony_create(&self, user_id: Option<Uuid>, state: serde_json::Value) -> Result<Uuid, RepoError> {
+        let id = Uuid::new_v4();
+
+        let mut tx = self.pool.begin().await?;
+
+        // login ceremonies are started anonymously and most are never finished,
+        // nothing else would remove them
+        sqlx::query!("DELETE FROM webauthn_ceremonies WHERE expires_at <= NOW()")
+            .execute(&mut *tx)
+            .await?;
+
+        // a user only needs the registration they started last
+        if let Some(user_id) = user_id {
+            sqlx::query!(
+                "DELETE FROM webauthn_ceremonies WHERE user_id = $1",
+                user_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "
+            INSERT INTO webauthn_ceremonies (id, user_id, state, expires_at)
+            VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))
+            ",
+            id,
+            user_id,
+            state,
+            WEBAUTHN_CEREMONY_EXP_MINUTES
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn webauthn_ceremony_take(&self, id: Uuid) -> Result<Option<WebauthnCeremony>, RepoError> {
+        let ceremony = sqlx::query_as!(
+            WebauthnCeremony,
+            "DELETE FROM webauthn_ceremonies WHERE id = $1 AND expires_at > NOW() RETURNING user_id, state",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(ceremony)
+    }
+
+    async fn webauthn_credential_create(&self, user_id: Uuid, credential_id: &[u8], passkey: serde_json::Value, name: &str) -> Result<Uuid, RepoError> {
+        let id = Uuid::new_v4();
+
+        sqlx::query!(
+            "INSERT INTO webauthn_credentials (id, user_id, credential_id, passkey, name) VALUES ($1, $2, $3, $4, $5)",
+            id,
+            user_id,
+            credential_id,
+            passkey,
+            name
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    async fn webauthn_credential_get(&self, credential_id: &[u8]) -> Result<Option<WebauthnCredential>, RepoError> {
+        let credential = sqlx::query_as!(
+            WebauthnCredential,
+            "
+            SELECT id, user_id, credential_id, passkey, name, created_at, last_used_at
+            FROM webauthn_credentials
+            WHERE credential_id = $1
+            ",
+            credential_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(credential)
+    }
+
+    async fn webauthn_credentials_for_user(&self, user_id: Uuid) -> Result<Vec<WebauthnCredential>, RepoError> {
+        let credentials = sqlx::query_as!(
+            WebauthnCredential,
+            "
+            SELECT id, user_id, credential_id, passkey, name, created_at, last_used_at
+            FROM webauthn_credentials
+            WHERE user_id = $1
+            ORDER BY created_at
+            ",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(credentials)
+    }
+
+    async fn webauthn_credential_mark_used(&self, id: Uuid, passkey: serde_json::Value) -> Result<(), RepoError> {
+        sqlx::query!(
+            "UPDATE webauthn_credentials SET passkey = $2, last_used_at = NOW() WHERE id = $1",
+            id,
+            passkey
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn webauthn_credential_delete(&self, id: Uuid, user_id: Uuid) -> Result<(), RepoError> {
+        sqlx::query!(
+            "DELETE FROM webauthn_credentials WHERE id = $1 AND user_id = $2",
+            id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
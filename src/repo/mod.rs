@@ -5,6 +5,7 @@ use sqlx::postgres::PgPoolOptions;
 
 pub mod error;
 pub mod infra;
+pub mod tenant;
 pub mod utils;
 
 
@@ -0,0 +1,49 @@
+//! Repository access scoped to a single tenant (an organization).
+//!
+//! Every query runs in a transaction that switches to the `app_tenant` database role and sets
+//! `app.tenant_id`, the row level security policies on tenant owned tables then hide every other
+//! tenant's rows. A query that forgets its `WHERE organization_id = ...` still only sees the tenant.
+//! Tables that are not granted to `app_tenant` can't be reached from here at all.
+
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{error::RepoError, Repository};
+
+
+#[derive(Clone)]
+pub struct TenantRepository {
+    pool: sqlx::PgPool,
+    tenant_id: Uuid,
+}
+
+impl Repository {
+    pub fn for_tenant(&self, tenant_id: Uuid) -> TenantRepository {
+        TenantRepository { pool: self.pool.clone(), tenant_id }
+    }
+}
+
+impl TenantRepository {
+    pub fn tenant_id(&self) -> Uuid {
+        self.tenant_id
+    }
+
+    /// A transaction scoped to the tenant, both settings are dropped again when it ends
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("SET LOCAL ROLE app_tenant")
+            .execute(&mut *tx)
+            .await?;
+
+        // SET LOCAL can't take parameters, set_config with is_local does the same
+        sqlx::query_scalar!(
+            "SELECT set_config('app.tenant_id', $1, true)",
+            self.tenant_id.to_string()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(tx)
+    }
+}
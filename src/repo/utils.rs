@@ -19,7 +19,7 @@ pub fn is_valid_password(password: &str) -> Result<bool, RepoError> {
     let min_length = 8;
     let has_uppercase = password.chars().any(|c| c.is_uppercase());
     let has_lowercase = password.chars().any(|c| c.is_lowercase());
-    let has_digit = password.chars().any(|c| c.is_digit(10));
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
     // let has_special_char = password.chars().any(|c| "!@#$%^&*()_+-=[]{}|;':\",.<>?/".contains(c));
 
     if password.len() >= min_length && has_uppercase && has_lowercase && has_digit {
@@ -41,14 +41,38 @@ pub fn is_valid_email(email: &str) -> Result<bool, RepoError> {
     // without regex
     let at = email.find('@');
     let dot = email.rfind('.');
-    if let Some(at) = at {
-        if let Some(dot) = dot {
-            if at < dot && dot < email.len() - 1 {
-                return Ok(true);
-            }
-        }
-    } 
+    if let (Some(at), Some(dot)) = (at, dot) && at < dot && dot < email.len() - 1 {
+        return Ok(true);
+    }
     Err(RepoError::ValidationError { 
-        body: Html(format!("Please enter a valid email."))
+        body: Html("Please enter a valid email.".to_string())
     })
 }
+
+/// Generates a random url safe token, e.g. for refresh tokens
+pub fn generate_token(length: usize) -> String {
+    use rand::Rng;
+
+    rand::rng()
+        .sample_iter(rand::distr::Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// Hashes a high entropy token for storage, unlike passwords these dont need argon2
+pub fn hash_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// The insert or update hit a unique constraint, e.g. an email that is already taken
+pub fn is_unique_violation(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::Database(e) if e.is_unique_violation())
+}
+
+/// The insert or update referenced a row that doesn't exist, e.g. an unknown role
+pub fn is_foreign_key_violation(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::Database(e) if e.is_foreign_key_violation())
+}
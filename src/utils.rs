@@ -1,6 +1,8 @@
+use std::{net::{IpAddr, SocketAddr}, sync::OnceLock};
+
 use askama::DynTemplate;
-use axum::{body::Body, response::{Html, IntoResponse}};
-use http::Response;
+use axum::{body::Body, extract::{ConnectInfo, FromRequestParts}, response::{Html, IntoResponse}};
+use http::{header, request::Parts, Response};
 
 
 use axum_extra::extract::cookie::Cookie;
@@ -13,24 +15,36 @@ pub async fn template_response(template: Box<dyn DynTemplate>) -> impl IntoRespo
     Html(
         template
             .dyn_render()
-            .map_err(|e| ServerError::Askama(e))
+            .map_err(ServerError::Askama)
         ).into_response()
 }
 
 
 pub fn extract_cookie_value(cookies_str: &str, cookie_name: &str) -> Option<String> {
     let cookies = Cookie::split_parse(cookies_str);
-    let token = cookies
+    cookies
         .into_iter()
         .find(|c| {
             c.as_ref().map(|c| c.name() == cookie_name).unwrap_or(false)
         })
         .map(|c| c.map(|c| c.value().to_string()))
         .transpose()
-        .ok()?;
-        
-    token
+        .ok()?
+}
 
+/// The token of an `Authorization: Bearer` header
+pub fn bearer_token(headers: &http::HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+/// Whether htmx sent the request, those get fragments instead of full pages
+pub fn is_htmx_request(headers: &http::HeaderMap) -> bool {
+    headers.get("HX-Request").is_some_and(|v| v == "true")
 }
 
 pub struct HxRedirect {
@@ -62,4 +76,105 @@ impl IntoResponse for HxRedirect {
             .body(Body::empty())
             .unwrap()
     }
-}
\ No newline at end of file
+}
+
+/// Set once from the config when the server starts, no proxy is trusted until then
+pub static TRUSTED_PROXIES: OnceLock<Vec<IpAddr>> = OnceLock::new();
+
+/// The device and network a request came from, recorded on sessions
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        let forwarded_for: Vec<&str> = parts
+            .headers
+            .get_all("X-Forwarded-For")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect();
+
+        let trusted = TRUSTED_PROXIES.get().map(Vec::as_slice).unwrap_or_default();
+        let ip = client_ip(peer, &forwarded_for, trusted).map(|ip| ip.to_string());
+
+        Ok(ClientInfo { user_agent, ip })
+    }
+}
+
+// every proxy appends the address it got the request from, so the header is read from the right
+// and the first hop that isn't one of our proxies is the client. Anything further left was
+// written by the client itself, and an unparsable hop ends the walk at the last trusted one
+fn client_ip(peer: Option<IpAddr>, forwarded_for: &[&str], trusted: &[IpAddr]) -> Option<IpAddr> {
+    let mut client = peer?.to_canonical();
+
+    let mut hops = forwarded_for.iter().flat_map(|v| v.split(',')).rev();
+    while trusted.contains(&client) {
+        let Some(hop) = hops.next().and_then(|hop| hop.trim().parse::<IpAddr>().ok()) else {
+            break;
+        };
+        client = hop.to_canonical();
+    }
+
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let client = client_ip(Some(ip("203.0.113.7")), &["198.51.100.1"], &[ip("10.0.0.1")]);
+        assert_eq!(client, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn takes_the_right_most_untrusted_hop() {
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+        // the client sent the first entry itself, the proxies appended the rest
+        let client = client_ip(Some(ip("10.0.0.1")), &["1.1.1.1, 198.51.100.9, 10.0.0.2"], &trusted);
+        assert_eq!(client, Some(ip("198.51.100.9")));
+    }
+
+    #[test]
+    fn reads_every_forwarded_for_header() {
+        let client = client_ip(Some(ip("10.0.0.1")), &["1.1.1.1", "198.51.100.9"], &[ip("10.0.0.1")]);
+        assert_eq!(client, Some(ip("198.51.100.9")));
+    }
+
+    #[test]
+    fn stops_at_hops_that_are_not_addresses() {
+        let too_long = "a".repeat(100);
+        let client = client_ip(Some(ip("10.0.0.1")), &[&too_long], &[ip("10.0.0.1")]);
+        assert_eq!(client, Some(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn falls_back_to_the_peer_without_the_header() {
+        let client = client_ip(Some(ip("::ffff:203.0.113.7")), &[], &[]);
+        assert_eq!(client, Some(ip("203.0.113.7")));
+    }
+}
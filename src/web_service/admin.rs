@@ -0,0 +1,28 @@
+use axum::{middleware::from_fn_with_state, routing::{get, post}, Router};
+
+use crate::features::{admin::{handlers::{api, views::AdminTemplate}, permissions::{UsersRead, UsersWrite}}, auth::permissions::require_permission};
+
+use super::WebService;
+use crate::rate_limit::{RateLimitKey, RateLimitLayer};
+
+
+pub struct AdminService;
+
+impl WebService for AdminService {
+    fn view_router(state: super::server::ServerState) -> axum::Router<super::server::ServerState> {
+        Router::new()
+            .route("/", get(AdminTemplate::handler))
+            .route_layer(from_fn_with_state(state.clone(), require_permission::<UsersRead>))
+            .with_state(state)
+    }
+
+    fn api_router(state: super::server::ServerState) -> axum::Router<super::server::ServerState> {
+        Router::new()
+            .route("/users/{id}/roles/{role}", post(api::grant_role))
+            .route("/users/{id}/roles/{role}/revoke", post(api::revoke_role))
+            .route("/users/{id}/sessions/revoke", post(api::revoke_sessions))
+            .route_layer(from_fn_with_state(state.clone(), require_permission::<UsersWrite>))
+            .route_layer(RateLimitLayer::new("api", state.config.rate_limit.api, RateLimitKey::UserOrIp, &state))
+            .with_state(state)
+    }
+}
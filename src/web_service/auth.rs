@@ -1,7 +1,9 @@
 
 use askama::Template;
-use axum::{response::Html, routing::{get, post}, Router};
+use axum::{middleware::from_fn_with_state, response::Html, routing::{get, post}, Router};
 use super::WebService;
+use crate::features::auth::middleware::require_session;
+use crate::rate_limit::{RateLimitKey, RateLimitLayer};
 
 use crate::features::auth::handlers::*;
 pub struct AuthService{}
@@ -10,21 +12,15 @@ impl WebService for AuthService {
     fn view_router(state: super::server::ServerState) -> axum::Router<super::server::ServerState> {
         Router::new()
 
-            .route("/login", get(|| async {
-                Html(views::authentication::LoginTemplate{}.render().map_err(
-                   |e| crate::ServerError::Askama(e)
-                ))
-            }))
-            .route("/register", get(|| async {
-                Html(views::authentication::RegisterTemplate{}.render().map_err(
-                   |e| crate::ServerError::Askama(e)
-                ))
-            }))
+            .route("/login", get(views::authentication::LoginTemplate::handler))
+            .route("/register", get(views::authentication::RegisterTemplate::handler))
+            .route("/magic-link", get(views::magic_link::MagicLinkTemplate::handler))
+            .route("/two-factor", get(views::two_factor::TwoFactorPage::handler))
             
             //this directs the user to email login -> enter code -> change password -> login
             .route("/reset-password", get(|| async {
                 Html(views::password_reset::EmailForm{}.render().map_err(
-                   |e| crate::ServerError::Askama(e)
+                   crate::ServerError::Askama
                 ))
             }))            
       
@@ -32,13 +28,67 @@ impl WebService for AuthService {
     }
 
     fn api_router(state: super::server::ServerState) -> axum::Router<super::server::ServerState> {
-        Router::new()
-            .route("/logout", post(api::authentication::logout))
-            .route("/login", post(api::authentication::login))
+        let limits = &state.config.rate_limit;
+
+        // these send emails, so they are limited the hardest to keep inboxes from being spammed
+        let email = Router::new()
             .route("/register", post(api::authentication::register))
             .route("/email-code", post(api::password_reset::email_code))
+            .route("/magic-link", post(api::magic_link::request))
+            .route("/verify-email/resend", post(api::email_verification::resend))
+            .route_layer(RateLimitLayer::new("email", limits.email, RateLimitKey::Ip, &state));
+
+        // the routes below that change how the account signs in take a browser session,
+        // a leaked personal access token must not be enough to take the account over
+        let email_change = Router::new()
+            .route("/change-email", post(api::email_change::request))
+            .route_layer(from_fn_with_state(state.clone(), require_session))
+            .route_layer(RateLimitLayer::new("email", limits.email, RateLimitKey::Ip, &state));
+
+        // these check passwords, codes or links
+        let credentials = Router::new()
+            .route("/login", post(api::authentication::login))
             .route("/code-login", post(api::password_reset::code_login))
             .route("/change-password", post(api::password_reset::change_password))
+            .route("/magic-link/verify", post(api::magic_link::verify))
+            .route("/verify-email", get(api::email_verification::verify))
+            .route("/change-email/confirm", get(api::email_change::confirm))
+            .route("/change-email/revert", get(api::email_change::revert))
+            .route("/two-factor/verify", post(api::two_factor::verify))
+            .route("/passkey/login/start", post(api::passkey::login_start))
+            .route("/passkey/login/finish", post(api::passkey::login_finish))
+            .route("/oidc/{provider}", get(api::oidc::start))
+            .route("/oidc/{provider}/callback", get(api::oidc::callback))
+            .route_layer(RateLimitLayer::new("auth", limits.auth, RateLimitKey::Ip, &state));
+
+        let two_factor = Router::new()
+            .route("/two-factor/confirm", post(api::two_factor::confirm))
+            .route("/two-factor/disable", post(api::two_factor::disable))
+            .route("/two-factor/recovery-codes", post(api::two_factor::regenerate_recovery_codes))
+            .route_layer(from_fn_with_state(state.clone(), require_session))
+            .route_layer(RateLimitLayer::new("auth", limits.auth, RateLimitKey::Ip, &state));
+
+        let account = Router::new()
+            .route("/two-factor/setup", post(api::two_factor::setup))
+            .route("/passkey/register/start", post(api::passkey::register_start))
+            .route("/passkey/register/finish", post(api::passkey::register_finish))
+            .route("/passkey/{id}/delete", post(api::passkey::delete))
+            .route("/tokens", post(api::personal_access_token::create))
+            .route("/tokens/{id}/revoke", post(api::personal_access_token::revoke))
+            .route_layer(from_fn_with_state(state.clone(), require_session))
+            .route_layer(RateLimitLayer::new("api", limits.api, RateLimitKey::UserOrIp, &state));
+
+        let logout = Router::new()
+            .route("/logout", post(api::authentication::logout))
+            .route_layer(RateLimitLayer::new("api", limits.api, RateLimitKey::UserOrIp, &state));
+
+        Router::new()
+            .merge(email)
+            .merge(email_change)
+            .merge(credentials)
+            .merge(two_factor)
+            .merge(account)
+            .merge(logout)
             .with_state(state)
     }
 }
\ No newline at end of file
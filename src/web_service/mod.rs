@@ -1,7 +1,9 @@
 use axum::Router;
 use server::ServerState;
 
+pub mod admin;
 pub mod auth;
+pub mod organization;
 pub mod server;
 pub mod user;
 
@@ -0,0 +1,39 @@
+use axum::{middleware::from_fn_with_state, routing::{get, post}, Router};
+
+use crate::features::{auth::middleware::require_session, organization::handlers::{api, views::InvitationTemplate}};
+
+use super::WebService;
+use crate::rate_limit::{RateLimitKey, RateLimitLayer};
+
+
+pub struct OrganizationService;
+
+impl WebService for OrganizationService {
+    fn view_router(state: super::server::ServerState) -> axum::Router<super::server::ServerState> {
+        Router::new()
+            .route("/invitations/accept", get(InvitationTemplate::handler))
+            .with_state(state)
+    }
+
+    fn api_router(state: super::server::ServerState) -> axum::Router<super::server::ServerState> {
+        let limits = &state.config.rate_limit;
+
+        // sends emails to addresses of the member's choosing
+        let email = Router::new()
+            .route("/invitations", post(api::invite))
+            .route_layer(RateLimitLayer::new("email", limits.email, RateLimitKey::UserOrIp, &state));
+
+        // these switch the session to the organization, which a personal access token doesn't have
+        let account = Router::new()
+            .route("/", post(api::create))
+            .route("/{id}/switch", post(api::switch))
+            .route("/invitations/accept", post(api::accept))
+            .route_layer(from_fn_with_state(state.clone(), require_session))
+            .route_layer(RateLimitLayer::new("api", limits.api, RateLimitKey::UserOrIp, &state));
+
+        Router::new()
+            .merge(email)
+            .merge(account)
+            .with_state(state)
+    }
+}
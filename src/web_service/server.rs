@@ -1,13 +1,14 @@
-use std::str::FromStr;
+use std::{net::SocketAddr, str::FromStr, sync::Arc};
 
-use crate::{config::ServerConfig, mailer::Mailer, repo::Repository};
+use crate::{config::ServerConfig, features::auth::{claims::{cookie::COOKIE_POLICY, keyring::{Keyring, KEYRING}}, csrf::csrf_protect, handlers::api::jwks, middleware::refresh_session, oidc::OidcClient}, mailer::Mailer, rate_limit::store::{InMemoryStore, RateLimitStore}, repo::Repository, utils::TRUSTED_PROXIES};
 use axum::{
     extract::MatchedPath, http::{
-        header::{AUTHORIZATION, CONTENT_TYPE},
+        header::{HeaderName, AUTHORIZATION, CONTENT_TYPE},
         Method, Request,
-    }, Router
+    }, routing::get, Router
 };
 use lettre::{transport::smtp::authentication::Credentials, Address};
+use webauthn_rs::{prelude::Url, Webauthn, WebauthnBuilder};
 use tower_http::{
     cors::CorsLayer,
     services::ServeDir,
@@ -19,14 +20,18 @@ use tracing_subscriber::{
     util::SubscriberInitExt,
 };
 
-use super::{auth::AuthService, user::UserService, WebService};
+use super::{admin::AdminService, auth::AuthService, organization::OrganizationService, user::UserService, WebService};
 
 
 #[derive(Clone)]
 pub struct ServerState {
     pub mailer: Mailer,
     pub repo: Repository,
-    config: ServerConfig
+    pub webauthn: Arc<Webauthn>,
+    // shared so connections to identity providers are pooled and their keys cached
+    pub oidc: Arc<OidcClient>,
+    pub rate_limit_store: Arc<dyn RateLimitStore>,
+    pub config: ServerConfig
 }
 impl ServerState {
     pub async fn initialize() -> Self {
@@ -36,14 +41,36 @@ impl ServerState {
         let mailer_credentials = Credentials::new(config.mailer.username.clone(), config.mailer.password.clone());
         let mailer_email = Address::from_str(&config.mailer.sender_email.clone()).expect("CONFIG SENDER EMAIL IS NOT VALID EMAIL");
         
-        let mailer = Mailer::new(mailer_credentials, mailer_email, config.mailer.host.clone(), config.mailer.port.clone(), config.full_sender_name().clone());
+        let mailer = Mailer::new(mailer_credentials, mailer_email, config.mailer.host.clone(), config.mailer.port, config.full_sender_name().clone());
+
+        let webauthn = Self::webauthn(&config.app.origin, &config.app.app_name);
+
+        let _ = TRUSTED_PROXIES.set(config.app.trusted_proxies.clone());
+
+        let _ = COOKIE_POLICY.set(config.cookies.clone());
+        let _ = KEYRING.set(Keyring::from_config(&config.jwt, &config.app.origin).expect("Could not load the jwt keys"));
 
         ServerState {
             repo,
+            webauthn: Arc::new(webauthn),
+            oidc: Arc::new(OidcClient::new(reqwest::Client::new())),
+            rate_limit_store: Arc::new(InMemoryStore::new()),
             config,
             mailer
         }
     }
+
+    /// Passkeys are bound to the host of the app origin
+    pub fn webauthn(origin: &str, app_name: &str) -> Webauthn {
+        let rp_origin = Url::parse(origin).expect("CONFIG ORIGIN IS NOT A VALID URL");
+        let rp_id = rp_origin.host_str().expect("CONFIG ORIGIN HAS NO HOST").to_string();
+
+        WebauthnBuilder::new(&rp_id, &rp_origin)
+            .expect("Could not configure webauthn")
+            .rp_name(app_name)
+            .build()
+            .expect("Could not configure webauthn")
+    }
 }
 
 pub struct Server {}
@@ -53,7 +80,10 @@ impl WebService for Server {
         Router::new()
             .merge(UserService::view_router(state.clone()))
             // .route("/", get(|| async {"Hello World!"}))
+            .route("/.well-known/jwks.json", get(jwks::jwks))
             .nest("/auth", AuthService::view_router(state.clone()))
+            .nest("/admin", AdminService::view_router(state.clone()))
+            .nest("/orgs", OrganizationService::view_router(state.clone()))
             .with_state(state.clone())
     }
     
@@ -62,6 +92,8 @@ impl WebService for Server {
             .merge(UserService::api_router(state.clone()))
             // .route("/", get(|| async {"Hello World!"}))
             .nest("/auth", AuthService::api_router(state.clone()))
+            .nest("/admin", AdminService::api_router(state.clone()))
+            .nest("/orgs", OrganizationService::api_router(state.clone()))
             .with_state(state.clone())
     }
 }
@@ -81,6 +113,9 @@ impl Server{
             .merge(Server::view_router(state.clone()))
             
             .nest("/api", Server::api_router(state.clone()))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), refresh_session))
+            // outside of the refresh so forged requests are turned away before anything happens
+            .layer(axum::middleware::from_fn(csrf_protect))
 
             .layer(Self::cors_layer(state.config.app.origin.clone()))
             .layer(
@@ -106,7 +141,7 @@ impl Server{
         
         let listener = tokio::net::TcpListener::bind(&bind_address).await?; 
         tracing::info!("Listening on {}", bind_address);        
-        axum::serve(listener, app.into_make_service()) 
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()) 
             .await
             .expect("Failed to start server");
 
@@ -122,7 +157,7 @@ impl Server{
             .allow_origin(origin.parse::<axum::http::HeaderValue>().expect("Failed to parse APP_ORIGIN"))
         //    .allow_origin(origin.parse::<axum::http::HeaderValue>().unwrap())
            .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE]) // Allow common methods
-           .allow_headers([CONTENT_TYPE, AUTHORIZATION]) // Allow common headers
+           .allow_headers([CONTENT_TYPE, AUTHORIZATION, HeaderName::from_static("x-csrf-token")]) // Allow common headers
     }
 
     fn init_tracing() {
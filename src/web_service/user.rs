@@ -1,8 +1,9 @@
-use axum::{routing::get, Router};
+use axum::{middleware::from_fn_with_state, routing::{get, post}, Router};
 
-use crate::features::user::handlers::views::{DashboardTemplate, IndexTemplate};
+use crate::features::{auth::middleware::require_session, user::handlers::{api, views::{DashboardTemplate, IndexTemplate}}};
 
 use super::WebService;
+use crate::rate_limit::{RateLimitKey, RateLimitLayer};
 
 
 pub struct UserService;
@@ -16,7 +17,22 @@ impl WebService for UserService {
     }
 
     fn api_router(state: super::server::ServerState) -> axum::Router<super::server::ServerState> {
+        let limits = &state.config.rate_limit;
+
+        let sessions = Router::new()
+            .route("/sessions", get(api::sessions))
+            .route_layer(RateLimitLayer::new("api", limits.api, RateLimitKey::UserOrIp, &state));
+
+        // signing sessions out is for the user at a browser, not for scripts
+        let revoke = Router::new()
+            .route("/sessions/{id}/revoke", post(api::revoke_session))
+            .route("/sessions/revoke-others", post(api::revoke_other_sessions))
+            .route_layer(from_fn_with_state(state.clone(), require_session))
+            .route_layer(RateLimitLayer::new("api", limits.api, RateLimitKey::UserOrIp, &state));
+
         Router::new()
+            .merge(sessions)
+            .merge(revoke)
             .with_state(state)
     }
 }
\ No newline at end of file
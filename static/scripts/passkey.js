@@ -0,0 +1,123 @@
+
+// the server speaks base64url for every binary field, the browser api wants ArrayBuffers
+function base64UrlToBuffer(value) {
+    const base64 = value.replace(/-/g, "+").replace(/_/g, "/");
+    const padded = base64 + "=".repeat((4 - base64.length % 4) % 4);
+    return Uint8Array.from(atob(padded), c => c.charCodeAt(0)).buffer;
+}
+
+function bufferToBase64Url(buffer) {
+    const bytes = String.fromCharCode(...new Uint8Array(buffer));
+    return btoa(bytes).replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "");
+}
+
+// htmx sends this from hx-headers, plain fetch calls have to add it themselves
+function csrfHeaders(headers = {}) {
+    let meta = document.querySelector('meta[name="csrf-token"]');
+    return { ...headers, "X-CSRF-Token": meta ? meta.content : "" };
+}
+
+function showPasskeyError(message) {
+    let target = document.getElementById("passkey-error") || document.getElementById("response-error");
+    if (target) {
+        target.textContent = message;
+    }
+}
+
+async function registerPasskey() {
+    if (!window.PublicKeyCredential) {
+        return showPasskeyError("Your browser does not support passkeys.");
+    }
+
+    try {
+        let start = await fetch("/api/auth/passkey/register/start", { method: "POST", headers: csrfHeaders() });
+        if (!start.ok) {
+            return showPasskeyError(await start.text());
+        }
+        let { ceremony_id, options } = await start.json();
+
+        let publicKey = options.publicKey;
+        publicKey.challenge = base64UrlToBuffer(publicKey.challenge);
+        publicKey.user.id = base64UrlToBuffer(publicKey.user.id);
+        (publicKey.excludeCredentials || []).forEach(c => c.id = base64UrlToBuffer(c.id));
+
+        let credential = await navigator.credentials.create({ publicKey });
+        let name = prompt("Name this passkey", navigator.platform || "Passkey");
+
+        let finish = await fetch("/api/auth/passkey/register/finish", {
+            method: "POST",
+            headers: csrfHeaders({ "Content-Type": "application/json" }),
+            body: JSON.stringify({
+                ceremony_id,
+                name,
+                credential: {
+                    id: credential.id,
+                    rawId: bufferToBase64Url(credential.rawId),
+                    type: credential.type,
+                    extensions: credential.getClientExtensionResults(),
+                    response: {
+                        attestationObject: bufferToBase64Url(credential.response.attestationObject),
+                        clientDataJSON: bufferToBase64Url(credential.response.clientDataJSON),
+                    },
+                },
+            }),
+        });
+
+        if (!finish.ok) {
+            return showPasskeyError(await finish.text());
+        }
+        document.getElementById("passkeys").innerHTML = await finish.text();
+        showPasskeyError("");
+    } catch (e) {
+        showPasskeyError("Passkey registration was cancelled.");
+    }
+}
+
+async function loginWithPasskey() {
+    if (!window.PublicKeyCredential) {
+        return showPasskeyError("Your browser does not support passkeys.");
+    }
+
+    try {
+        let start = await fetch("/api/auth/passkey/login/start", { method: "POST", headers: csrfHeaders() });
+        if (!start.ok) {
+            return showPasskeyError(await start.text());
+        }
+        let { ceremony_id, options } = await start.json();
+
+        let publicKey = options.publicKey;
+        publicKey.challenge = base64UrlToBuffer(publicKey.challenge);
+        (publicKey.allowCredentials || []).forEach(c => c.id = base64UrlToBuffer(c.id));
+
+        let credential = await navigator.credentials.get({ publicKey });
+
+        let finish = await fetch("/api/auth/passkey/login/finish", {
+            method: "POST",
+            headers: csrfHeaders({ "Content-Type": "application/json" }),
+            body: JSON.stringify({
+                ceremony_id,
+                // checked by the server, only local paths are followed
+                next: new URLSearchParams(window.location.search).get("next"),
+                credential: {
+                    id: credential.id,
+                    rawId: bufferToBase64Url(credential.rawId),
+                    type: credential.type,
+                    extensions: credential.getClientExtensionResults(),
+                    response: {
+                        authenticatorData: bufferToBase64Url(credential.response.authenticatorData),
+                        clientDataJSON: bufferToBase64Url(credential.response.clientDataJSON),
+                        signature: bufferToBase64Url(credential.response.signature),
+                        userHandle: credential.response.userHandle ? bufferToBase64Url(credential.response.userHandle) : null,
+                    },
+                },
+            }),
+        });
+
+        if (!finish.ok) {
+            return showPasskeyError(await finish.text());
+        }
+        window.location.href = finish.headers.get("HX-Redirect") || "/dashboard";
+    } catch (e) {
+        showPasskeyError("Passkey sign in was cancelled.");
+    }
+}
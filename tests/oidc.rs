@@ -0,0 +1,357 @@
+//! Signs in against a fake identity provider served on a local port, covering discovery,
+//! the code exchange and id token validation the way the oidc callback runs them.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{extract::State, routing::{get, post}, Form, Json, Router};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use core_lib::{
+    config::OidcProviderConfig,
+    features::auth::{
+        claims::oidc::OidcStateClaim,
+        oidc::{self, error::OidcError, OidcClient},
+    },
+};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde_json::{json, Value};
+
+const CLIENT_ID: &str = "test-client";
+const REDIRECT_URI: &str = "http://localhost:8000/api/auth/oidc/test/callback";
+
+struct SigningKey {
+    kid: String,
+    pkcs8: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl SigningKey {
+    fn generate(kid: &str) -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+
+        Self {
+            kid: kid.to_string(),
+            pkcs8: pkcs8.as_ref().to_vec(),
+            public_key: pair.public_key().as_ref().to_vec(),
+        }
+    }
+
+    fn jwk(&self) -> Value {
+        // an uncompressed point, 0x04 followed by x and y
+        let (x, y) = self.public_key[1..].split_at(32);
+
+        json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "alg": "ES256",
+            "use": "sig",
+            "kid": self.kid,
+            "x": URL_SAFE_NO_PAD.encode(x),
+            "y": URL_SAFE_NO_PAD.encode(y),
+        })
+    }
+
+    fn sign(&self, claims: &Value) -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(self.kid.clone());
+
+        jsonwebtoken::encode(&header, claims, &EncodingKey::from_ec_der(&self.pkcs8)).unwrap()
+    }
+}
+
+/// What the fake provider publishes and hands out, tests change it between requests
+struct Idp {
+    issuer: String,
+    // the issuer its discovery document claims, a real provider names itself
+    advertised_issuer: Mutex<String>,
+    published_keys: Mutex<Vec<Value>>,
+    id_token: Mutex<Option<String>>,
+    token_requests: Mutex<Vec<Vec<(String, String)>>>,
+    discovery_hits: AtomicUsize,
+    jwks_hits: AtomicUsize,
+}
+
+impl Idp {
+    async fn start() -> Arc<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let idp = Arc::new(Self {
+            advertised_issuer: Mutex::new(issuer.clone()),
+            issuer,
+            published_keys: Mutex::default(),
+            id_token: Mutex::default(),
+            token_requests: Mutex::default(),
+            discovery_hits: AtomicUsize::new(0),
+            jwks_hits: AtomicUsize::new(0),
+        });
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(idp.clone());
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        idp
+    }
+
+    fn provider(&self) -> OidcProviderConfig {
+        OidcProviderConfig {
+            display_name: "Test".to_string(),
+            issuer: self.issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: "test-secret".to_string(),
+            scopes: vec!["openid".to_string(), "email".to_string()],
+        }
+    }
+
+    fn publish(&self, keys: &[&SigningKey]) {
+        *self.published_keys.lock().unwrap() = keys.iter().map(|key| key.jwk()).collect();
+    }
+
+    fn hand_out(&self, id_token: String) {
+        *self.id_token.lock().unwrap() = Some(id_token);
+    }
+
+    fn id_token_claims(&self, nonce: &str) -> Value {
+        let now = chrono::Utc::now().timestamp();
+
+        json!({
+            "iss": self.issuer,
+            "aud": CLIENT_ID,
+            "sub": "subject-1",
+            "email": "alice@example.com",
+            "email_verified": true,
+            "name": "Alice",
+            "nonce": nonce,
+            "iat": now,
+            "exp": now + 300,
+        })
+    }
+}
+
+async fn discovery(State(idp): State<Arc<Idp>>) -> Json<Value> {
+    idp.discovery_hits.fetch_add(1, Ordering::SeqCst);
+
+    Json(json!({
+        "issuer": *idp.advertised_issuer.lock().unwrap(),
+        "authorization_endpoint": format!("{}/authorize", idp.issuer),
+        "token_endpoint": format!("{}/token", idp.issuer),
+        "jwks_uri": format!("{}/jwks", idp.issuer),
+    }))
+}
+
+async fn jwks(State(idp): State<Arc<Idp>>) -> Json<Value> {
+    idp.jwks_hits.fetch_add(1, Ordering::SeqCst);
+
+    Json(json!({ "keys": *idp.published_keys.lock().unwrap() }))
+}
+
+async fn token(State(idp): State<Arc<Idp>>, Form(form): Form<Vec<(String, String)>>) -> Json<Value> {
+    idp.token_requests.lock().unwrap().push(form);
+
+    Json(json!({
+        "access_token": "access",
+        "token_type": "Bearer",
+        "id_token": *idp.id_token.lock().unwrap(),
+    }))
+}
+
+fn client() -> OidcClient {
+    OidcClient::new(reqwest::Client::new())
+}
+
+async fn sign_in(client: &OidcClient, idp: &Idp, claim: &OidcStateClaim) -> Result<oidc::IdTokenClaims, OidcError> {
+    let provider = idp.provider();
+    let metadata = client.discover(&provider).await?;
+
+    client.exchange_code(&metadata, &provider, REDIRECT_URI, "the-code", claim).await
+}
+
+#[tokio::test]
+async fn signs_in_with_a_token_from_the_provider() {
+    let idp = Idp::start().await;
+    let key = SigningKey::generate("key-1");
+    idp.publish(&[&key]);
+
+    let client = client();
+    let claim = OidcStateClaim::new("test".to_string(), None);
+    idp.hand_out(key.sign(&idp.id_token_claims(&claim.nonce)));
+
+    let metadata = client.discover(&idp.provider()).await.unwrap();
+    let url = oidc::authorization_url(&metadata, &idp.provider(), REDIRECT_URI, &claim).unwrap();
+    let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    assert!(url.as_str().starts_with(&format!("{}/authorize", idp.issuer)));
+    assert!(params.contains(&("state".to_string(), claim.state.clone())));
+    assert!(params.contains(&("code_challenge".to_string(), oidc::code_challenge(&claim.code_verifier))));
+
+    let identity = sign_in(&client, &idp, &claim).await.unwrap();
+    assert_eq!(identity.sub, "subject-1");
+    assert_eq!(identity.email.as_deref(), Some("alice@example.com"));
+    assert!(identity.email_verified);
+
+    // the code is only redeemable together with the verifier behind the challenge
+    let request = idp.token_requests.lock().unwrap().pop().unwrap();
+    assert!(request.contains(&("code_verifier".to_string(), claim.code_verifier.clone())));
+    assert!(request.contains(&("code".to_string(), "the-code".to_string())));
+}
+
+#[tokio::test]
+async fn keeps_the_return_url_to_itself() {
+    let idp = Idp::start().await;
+    let client = client();
+    let claim = OidcStateClaim::new("test".to_string(), Some("/orgs/invitations/accept?token=abc".to_string()));
+
+    // only the state cookie brings it back to the callback, the provider never sees it
+    let metadata = client.discover(&idp.provider()).await.unwrap();
+    let url = oidc::authorization_url(&metadata, &idp.provider(), REDIRECT_URI, &claim).unwrap();
+    assert!(!url.as_str().contains("invitations"));
+}
+
+#[tokio::test]
+async fn caches_discovery_and_keys() {
+    let idp = Idp::start().await;
+    let key = SigningKey::generate("key-1");
+    idp.publish(&[&key]);
+
+    let client = client();
+    for _ in 0..3 {
+        let claim = OidcStateClaim::new("test".to_string(), None);
+        idp.hand_out(key.sign(&idp.id_token_claims(&claim.nonce)));
+        sign_in(&client, &idp, &claim).await.unwrap();
+    }
+
+    assert_eq!(idp.discovery_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(idp.jwks_hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn fetches_again_once_the_cache_expired() {
+    let idp = Idp::start().await;
+    let key = SigningKey::generate("key-1");
+    idp.publish(&[&key]);
+
+    let client = OidcClient::with_ttl(reqwest::Client::new(), Duration::ZERO);
+    for _ in 0..2 {
+        let claim = OidcStateClaim::new("test".to_string(), None);
+        idp.hand_out(key.sign(&idp.id_token_claims(&claim.nonce)));
+        sign_in(&client, &idp, &claim).await.unwrap();
+    }
+
+    assert_eq!(idp.discovery_hits.load(Ordering::SeqCst), 2);
+    assert_eq!(idp.jwks_hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn picks_up_rotated_keys_before_the_cache_expires() {
+    let idp = Idp::start().await;
+    let old_key = SigningKey::generate("key-1");
+    idp.publish(&[&old_key]);
+
+    let client = client();
+    let claim = OidcStateClaim::new("test".to_string(), None);
+    idp.hand_out(old_key.sign(&idp.id_token_claims(&claim.nonce)));
+    sign_in(&client, &idp, &claim).await.unwrap();
+
+    let new_key = SigningKey::generate("key-2");
+    idp.publish(&[&new_key]);
+
+    let claim = OidcStateClaim::new("test".to_string(), None);
+    idp.hand_out(new_key.sign(&idp.id_token_claims(&claim.nonce)));
+    sign_in(&client, &idp, &claim).await.unwrap();
+
+    assert_eq!(idp.jwks_hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn refuses_a_discovery_document_for_another_issuer() {
+    let idp = Idp::start().await;
+    *idp.advertised_issuer.lock().unwrap() = "https://evil.example".to_string();
+
+    let result = client().discover(&idp.provider()).await;
+
+    assert!(matches!(result, Err(OidcError::IssuerMismatch)));
+}
+
+#[tokio::test]
+async fn refuses_a_token_for_another_sign_in() {
+    let idp = Idp::start().await;
+    let key = SigningKey::generate("key-1");
+    idp.publish(&[&key]);
+
+    let claim = OidcStateClaim::new("test".to_string(), None);
+    let other_claim = OidcStateClaim::new("test".to_string(), None);
+    idp.hand_out(key.sign(&idp.id_token_claims(&other_claim.nonce)));
+
+    let result = sign_in(&client(), &idp, &claim).await;
+
+    assert!(matches!(result, Err(OidcError::NonceMismatch)));
+}
+
+#[tokio::test]
+async fn refuses_a_token_signed_with_an_unpublished_key() {
+    let idp = Idp::start().await;
+    let key = SigningKey::generate("key-1");
+    idp.publish(&[&key]);
+
+    // same kid, different key
+    let forged_key = SigningKey::generate("key-1");
+    let claim = OidcStateClaim::new("test".to_string(), None);
+    idp.hand_out(forged_key.sign(&idp.id_token_claims(&claim.nonce)));
+    assert!(matches!(sign_in(&client(), &idp, &claim).await, Err(OidcError::IdToken(_))));
+
+    let unknown_key = SigningKey::generate("key-9");
+    idp.hand_out(unknown_key.sign(&idp.id_token_claims(&claim.nonce)));
+    assert!(matches!(sign_in(&client(), &idp, &claim).await, Err(OidcError::SigningKeyNotFound)));
+}
+
+#[tokio::test]
+async fn refuses_a_token_meant_for_another_client_or_issuer() {
+    let idp = Idp::start().await;
+    let key = SigningKey::generate("key-1");
+    idp.publish(&[&key]);
+
+    let client = client();
+    let claim = OidcStateClaim::new("test".to_string(), None);
+
+    let mut claims = idp.id_token_claims(&claim.nonce);
+    claims["aud"] = json!("another-client");
+    idp.hand_out(key.sign(&claims));
+    assert!(matches!(sign_in(&client, &idp, &claim).await, Err(OidcError::IdToken(_))));
+
+    let mut claims = idp.id_token_claims(&claim.nonce);
+    claims["iss"] = json!("https://evil.example");
+    idp.hand_out(key.sign(&claims));
+    assert!(matches!(sign_in(&client, &idp, &claim).await, Err(OidcError::IdToken(_))));
+}
+
+#[tokio::test]
+async fn refuses_a_token_signed_with_a_shared_secret() {
+    let idp = Idp::start().await;
+    let key = SigningKey::generate("key-1");
+    idp.publish(&[&key]);
+
+    let claim = OidcStateClaim::new("test".to_string(), None);
+    let token = jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        &idp.id_token_claims(&claim.nonce),
+        &EncodingKey::from_secret(b"test-secret"),
+    )
+    .unwrap();
+    idp.hand_out(token);
+
+    assert!(matches!(sign_in(&client(), &idp, &claim).await, Err(OidcError::SigningKeyNotFound)));
+}
@@ -0,0 +1,150 @@
+//! Runs the passkey ceremonies the way `handlers::api::passkey` does, with a software
+//! authenticator in place of the browser. The ceremony state and the passkey go through JSON
+//! like they do on their way into and out of the database.
+
+use core_lib::web_service::server::ServerState;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+use webauthn_rs::prelude::{
+    AuthenticationResult, Base64UrlSafeData, DiscoverableAuthentication, DiscoverableKey, Passkey,
+    PasskeyRegistration, PublicKeyCredential, RequestChallengeResponse, Url, Uuid, Webauthn,
+};
+
+const ORIGIN: &str = "http://localhost:8000";
+
+type Authenticator = WebauthnAuthenticator<SoftPasskey>;
+
+fn webauthn() -> Webauthn {
+    ServerState::webauthn(ORIGIN, "Test App")
+}
+
+fn authenticator() -> Authenticator {
+    // passkeys require user verification, which the soft authenticator can only pretend to do
+    WebauthnAuthenticator::new(SoftPasskey::new(true))
+}
+
+fn through_json<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    serde_json::from_value(serde_json::to_value(value).unwrap()).unwrap()
+}
+
+fn register(webauthn: &Webauthn, authenticator: &mut Authenticator, user_id: Uuid) -> Passkey {
+    let (options, registration) = webauthn
+        .start_passkey_registration(user_id, "alice@example.com", "Alice", None)
+        .unwrap();
+    let registration: PasskeyRegistration = through_json(&registration);
+
+    let credential = authenticator.do_registration(Url::parse(ORIGIN).unwrap(), options).unwrap();
+    let passkey = webauthn.finish_passkey_registration(&credential, &registration).unwrap();
+
+    through_json(&passkey)
+}
+
+// what the login page posts back, signed for the given ceremony's challenge
+fn assert_with(authenticator: &mut Authenticator, options: RequestChallengeResponse, passkey: &Passkey, user_id: Uuid) -> PublicKeyCredential {
+    // a browser lets the user pick one of their discoverable passkeys, the soft authenticator has to be told
+    let mut options = serde_json::to_value(options).unwrap();
+    options["publicKey"]["allowCredentials"] = json!([{ "type": "public-key", "id": passkey.cred_id() }]);
+    let options: RequestChallengeResponse = serde_json::from_value(options).unwrap();
+
+    let mut credential = authenticator.do_authentication(Url::parse(ORIGIN).unwrap(), options).unwrap();
+
+    // a discoverable passkey hands back the user handle it was registered with
+    credential.response.user_handle = Some(Base64UrlSafeData::from(user_id.as_bytes().to_vec()));
+
+    credential
+}
+
+fn sign_in(webauthn: &Webauthn, credential: &PublicKeyCredential, authentication: DiscoverableAuthentication, passkey: &Passkey) -> Result<AuthenticationResult, webauthn_rs::prelude::WebauthnError> {
+    webauthn.finish_discoverable_authentication(credential, through_json(&authentication), &[DiscoverableKey::from(passkey)])
+}
+
+#[test]
+fn registers_and_signs_in_without_a_username() {
+    let webauthn = webauthn();
+    let mut authenticator = authenticator();
+    let user_id = Uuid::new_v4();
+
+    let mut passkey = register(&webauthn, &mut authenticator, user_id);
+
+    let (options, authentication) = webauthn.start_discoverable_authentication().unwrap();
+    let credential = assert_with(&mut authenticator, options, &passkey, user_id);
+
+    // login_finish looks the passkey up by these before verifying it
+    let (identified_user, credential_id) = webauthn.identify_discoverable_authentication(&credential).unwrap();
+    assert_eq!(identified_user, user_id);
+    assert_eq!(credential_id, passkey.cred_id().as_ref());
+
+    let result = sign_in(&webauthn, &credential, authentication, &passkey).unwrap();
+    assert!(result.user_verified());
+    assert_eq!(passkey.update_credential(&result), Some(true));
+}
+
+#[test]
+fn an_assertion_only_finishes_its_own_ceremony() {
+    let webauthn = webauthn();
+    let mut authenticator = authenticator();
+    let user_id = Uuid::new_v4();
+
+    let passkey = register(&webauthn, &mut authenticator, user_id);
+
+    let (options, _) = webauthn.start_discoverable_authentication().unwrap();
+    let credential = assert_with(&mut authenticator, options, &passkey, user_id);
+
+    let (_, other_authentication) = webauthn.start_discoverable_authentication().unwrap();
+    assert!(sign_in(&webauthn, &credential, other_authentication, &passkey).is_err());
+}
+
+#[test]
+fn refuses_an_assertion_checked_against_someone_elses_passkey() {
+    let webauthn = webauthn();
+    let mut authenticator = authenticator();
+    let user_id = Uuid::new_v4();
+
+    let passkey = register(&webauthn, &mut authenticator, user_id);
+    let other_passkey = register(&webauthn, &mut self::authenticator(), Uuid::new_v4());
+
+    let (options, authentication) = webauthn.start_discoverable_authentication().unwrap();
+    let credential = assert_with(&mut authenticator, options, &passkey, user_id);
+
+    assert!(sign_in(&webauthn, &credential, authentication, &other_passkey).is_err());
+}
+
+#[test]
+fn a_registration_only_finishes_its_own_ceremony() {
+    let webauthn = webauthn();
+    let mut authenticator = authenticator();
+    let user_id = Uuid::new_v4();
+
+    let (options, _) = webauthn
+        .start_passkey_registration(user_id, "alice@example.com", "Alice", None)
+        .unwrap();
+    let credential = authenticator.do_registration(Url::parse(ORIGIN).unwrap(), options).unwrap();
+
+    let (_, other_registration) = webauthn
+        .start_passkey_registration(user_id, "alice@example.com", "Alice", None)
+        .unwrap();
+
+    assert!(webauthn.finish_passkey_registration(&credential, &other_registration).is_err());
+}
+
+#[test]
+fn refuses_passkeys_made_for_another_origin() {
+    let webauthn = webauthn();
+    let mut authenticator = authenticator();
+
+    let (options, registration) = webauthn
+        .start_passkey_registration(Uuid::new_v4(), "alice@example.com", "Alice", None)
+        .unwrap();
+
+    // a phishing page on another host can't get a passkey for our relying party
+    assert!(authenticator.do_registration(Url::parse("https://evil.example").unwrap(), options.clone()).is_err());
+
+    let other = ServerState::webauthn("https://example.org", "Other App");
+    let (other_options, _) = other
+        .start_passkey_registration(Uuid::new_v4(), "alice@example.com", "Alice", None)
+        .unwrap();
+    let credential = authenticator.do_registration(Url::parse("https://example.org").unwrap(), other_options).unwrap();
+
+    assert!(webauthn.finish_passkey_registration(&credential, &registration).is_err());
+}